@@ -2,20 +2,122 @@ use crate::game::Game;
 use crate::core::mov::MoveList;
 use crate::board::{Bitboard};
 use crate::Piece;
+use crate::search::pst::get_piece_at;
+
+/// Which moves to generate. Captures include en passant and every promotion, quiets are
+/// everything else, castling included.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GenType {
+    All,
+    Captures,
+    Quiets,
+}
+
+impl GenType {
+    /// Squares a non-pawn piece may move to.
+    fn targets(self, game: &Game) -> u64 {
+        let (my_pieces, enemy_pieces) = if game.is_white_turn {
+            (game.board.white_pieces(), game.board.black_pieces())
+        } else {
+            (game.board.black_pieces(), game.board.white_pieces())
+        };
+        match self {
+            GenType::All => !my_pieces,
+            GenType::Captures => enemy_pieces,
+            GenType::Quiets => !(my_pieces | enemy_pieces),
+        }
+    }
+}
 
 pub fn generate_pseudo_legal_moves(game: &Game) -> MoveList {
+    generate_moves(game, GenType::All)
+}
+
+pub fn generate_pseudo_legal_captures(game: &Game) -> MoveList {
+    generate_moves(game, GenType::Captures)
+}
+
+pub fn generate_pseudo_legal_quiets(game: &Game) -> MoveList {
+    generate_moves(game, GenType::Quiets)
+}
+
+fn generate_moves(game: &Game, gen_type: GenType) -> MoveList {
     let mut moves = MoveList::new();
-    generate_pawn_moves(game, &mut moves);
-    generate_knight_moves(game, &mut moves);
-    generate_bishop_moves(game, &mut moves);
-    generate_rook_moves(game, &mut moves);
-    generate_queen_moves(game, &mut moves);
-    generate_king_moves(game, &mut moves);
+    let targets = gen_type.targets(game);
+    generate_pawn_moves(game, &mut moves, gen_type);
+    generate_knight_moves(game, &mut moves, targets);
+    generate_bishop_moves(game, &mut moves, targets);
+    generate_rook_moves(game, &mut moves, targets);
+    generate_queen_moves(game, &mut moves, targets);
+    generate_king_moves(game, &mut moves, targets, gen_type != GenType::Captures);
     moves
 }
 
+impl Game {
+    /// Whether `generate_pseudo_legal_moves` would return `mv`, found without generating
+    /// anything. Moves from the transposition table or killer slots may come from another
+    /// position and must pass this before `make_move_unchecked`.
+    pub fn is_pseudo_legal(&self, mv: (usize, usize, Option<Piece>)) -> bool {
+        let (from, to, promo) = mv;
+        if from >= 64 || to >= 64 {
+            return false;
+        }
+        let (my_pieces, enemy_pieces, colour) = if self.is_white_turn {
+            (self.board.white_pieces(), self.board.black_pieces(), 0)
+        } else {
+            (self.board.black_pieces(), self.board.white_pieces(), 1)
+        };
+        let (from_mask, to_mask) = (1u64 << from, 1u64 << to);
+        if my_pieces & from_mask == 0 || my_pieces & to_mask != 0 {
+            return false;
+        }
+        let Some(piece) = get_piece_at(self, from) else {
+            return false;
+        };
+        let all_pieces = self.board.all_pieces();
+
+        if piece == Piece::Pawn {
+            let promotes = to / 8 == if self.is_white_turn { 7 } else { 0 };
+            let valid_promo = match promo {
+                None => !promotes,
+                Some(p) => promotes && !matches!(p, Piece::King | Piece::Pawn),
+            };
+            if !valid_promo {
+                return false;
+            }
+            if Bitboard::get_pawn_attacks(colour, from) & to_mask != 0 {
+                return enemy_pieces & to_mask != 0 || self.en_passent == Some(to);
+            }
+            let single_push = if self.is_white_turn { from + 8 } else { from - 8 };
+            return Bitboard::get_pawn_pushes(colour, from) & to_mask != 0
+                && all_pieces & (to_mask | 1u64 << single_push) == 0;
+        }
+        if promo.is_some() {
+            return false;
+        }
+
+        let attacks = match piece {
+            Piece::Knight => self.board.get_knight_attacks(from),
+            Piece::Bishop => Bitboard::get_bishop_attacks(from, all_pieces),
+            Piece::Rook => Bitboard::get_rook_attacks(from, all_pieces),
+            Piece::Queen => {
+                Bitboard::get_rook_attacks(from, all_pieces)
+                    | Bitboard::get_bishop_attacks(from, all_pieces)
+            }
+            Piece::King => {
+                if from.abs_diff(to) == 2 && from == if self.is_white_turn { 4 } else { 60 } {
+                    return can_castle(self, to);
+                }
+                self.board.get_king_attacks(from)
+            }
+            Piece::Pawn => unreachable!(),
+        };
+        attacks & to_mask != 0
+    }
+}
+
 // Biggest crutch since atm it isn't pre computed
-fn generate_pawn_moves(game: &Game, moves: &mut MoveList) {
+fn generate_pawn_moves(game: &Game, moves: &mut MoveList, gen_type: GenType) {
     let (my_pawns, enemy_pieces, rank_7, _rank_2, _push_dir, colour) = if game.is_white_turn {
         (
             game.board.white_pawns,
@@ -37,12 +139,24 @@ fn generate_pawn_moves(game: &Game, moves: &mut MoveList) {
     };
 
     let all_pieces = game.board.all_pieces();
+    let captures = gen_type != GenType::Quiets;
+    let quiets = gen_type != GenType::Captures;
     let mut pawns = my_pawns;
 
     while pawns != 0 {
         let from = pawns.trailing_zeros() as usize;
 
         let mut push_targets = Bitboard::get_pawn_pushes(colour, from) & !all_pieces;
+        // A double push cannot jump over a piece on the square in between.
+        let single_push = if game.is_white_turn { from + 8 } else { from - 8 };
+        if all_pieces & (1u64 << single_push) != 0 {
+            push_targets = 0;
+        }
+        // Promotions count as captures, plain pushes as quiets.
+        let promotes = from / 8 == rank_7;
+        if (promotes && !captures) || (!promotes && !quiets) {
+            push_targets = 0;
+        }
         while push_targets != 0 {
             let to = push_targets.trailing_zeros() as usize;
 
@@ -59,6 +173,9 @@ fn generate_pawn_moves(game: &Game, moves: &mut MoveList) {
         }
 
         let mut capture_targets = Bitboard::get_pawn_attacks(colour, from) & enemy_pieces;
+        if !captures {
+            capture_targets = 0;
+        }
 
         while capture_targets != 0 {
             let to = capture_targets.trailing_zeros() as usize;
@@ -78,7 +195,7 @@ fn generate_pawn_moves(game: &Game, moves: &mut MoveList) {
         pawns &= pawns - 1;
     }
 
-    if let Some(ep_square) = game.en_passent {
+    if let Some(ep_square) = game.en_passent.filter(|_| captures) {
         let required_rank = if game.is_white_turn { 4 } else { 3 };
         let ep_file = ep_square % 8;
         if ep_file > 0 {
@@ -106,17 +223,17 @@ fn generate_pawn_moves(game: &Game, moves: &mut MoveList) {
 
 // Rest of these work the same
 // calls the pre computed bitboard
-fn generate_knight_moves(game: &Game, moves: &mut MoveList) {
-    let (my_knights, my_pieces) = if game.is_white_turn {
-        (game.board.white_knight, game.board.white_pieces())
+fn generate_knight_moves(game: &Game, moves: &mut MoveList, targets: u64) {
+    let my_knights = if game.is_white_turn {
+        game.board.white_knight
     } else {
-        (game.board.black_knight, game.board.black_pieces())
+        game.board.black_knight
     };
     let mut knights = my_knights;
     while knights != 0 {
         let from = knights.trailing_zeros() as usize;
         let mut attacks = game.board.get_knight_attacks(from);
-        attacks &= !my_pieces;
+        attacks &= targets;
         while attacks != 0 {
             let to = attacks.trailing_zeros() as usize;
             moves.add(from, to, None);
@@ -126,57 +243,55 @@ fn generate_knight_moves(game: &Game, moves: &mut MoveList) {
     }
 }
 
-fn generate_bishop_moves(game: &Game, moves: &mut MoveList) {
-    let (my_bishops, my_pieces) = if game.is_white_turn {
-        (game.board.white_bishop, game.board.white_pieces())
+fn generate_bishop_moves(game: &Game, moves: &mut MoveList, targets: u64) {
+    let my_bishops = if game.is_white_turn {
+        game.board.white_bishop
     } else {
-        (game.board.black_bishop, game.board.black_pieces())
+        game.board.black_bishop
     };
 
     let mut bishops = my_bishops;
     while bishops != 0 {
         let from = bishops.trailing_zeros() as usize;
         let mut attacks = Bitboard::get_bishop_attacks(from, game.board.all_pieces());
-        attacks &= !my_pieces;
+        attacks &= targets;
 
-        let mut targets = attacks;
-        while targets != 0 {
-            let to = targets.trailing_zeros() as usize;
+        while attacks != 0 {
+            let to = attacks.trailing_zeros() as usize;
             moves.add(from, to, None);
-            targets &= targets - 1;
+            attacks &= attacks - 1;
         }
         bishops &= bishops - 1;
     }
 }
 
-fn generate_rook_moves(game: &Game, moves: &mut MoveList) {
-    let (my_rooks, my_pieces) = if game.is_white_turn {
-        (game.board.white_rook, game.board.white_pieces())
+fn generate_rook_moves(game: &Game, moves: &mut MoveList, targets: u64) {
+    let my_rooks = if game.is_white_turn {
+        game.board.white_rook
     } else {
-        (game.board.black_rook, game.board.black_pieces())
+        game.board.black_rook
     };
 
     let mut rooks = my_rooks;
     while rooks != 0 {
         let from = rooks.trailing_zeros() as usize;
         let mut attacks = Bitboard::get_rook_attacks(from, game.board.all_pieces());
-        attacks &= !my_pieces;
+        attacks &= targets;
 
-        let mut targets = attacks;
-        while targets != 0 {
-            let to = targets.trailing_zeros() as usize;
+        while attacks != 0 {
+            let to = attacks.trailing_zeros() as usize;
             moves.add(from, to, None);
-            targets &= targets - 1;
+            attacks &= attacks - 1;
         }
         rooks &= rooks - 1;
     }
 }
 
-fn generate_queen_moves(game: &Game, moves: &mut MoveList) {
-    let (my_queens, my_pieces) = if game.is_white_turn {
-        (game.board.white_queen, game.board.white_pieces())
+fn generate_queen_moves(game: &Game, moves: &mut MoveList, targets: u64) {
+    let my_queens = if game.is_white_turn {
+        game.board.white_queen
     } else {
-        (game.board.black_queen, game.board.black_pieces())
+        game.board.black_queen
     };
 
     let mut queens = my_queens;
@@ -188,7 +303,7 @@ fn generate_queen_moves(game: &Game, moves: &mut MoveList) {
         let mut attacks = Bitboard::get_rook_attacks(from, blockers)
             | Bitboard::get_bishop_attacks(from, blockers);
 
-        attacks &= !my_pieces; // Can't capture your own pieces
+        attacks &= targets; // Never includes our own pieces
 
         while attacks != 0 {
             let to = attacks.trailing_zeros() as usize;
@@ -199,53 +314,63 @@ fn generate_queen_moves(game: &Game, moves: &mut MoveList) {
     }
 }
 
-fn generate_king_moves(game: &Game, moves: &mut MoveList) {
-    let (my_king, my_pieces) = if game.is_white_turn {
-        (game.board.white_king, game.board.white_pieces())
+fn generate_king_moves(game: &Game, moves: &mut MoveList, targets: u64, castling: bool) {
+    let my_king = if game.is_white_turn {
+        game.board.white_king
     } else {
-        (game.board.black_king, game.board.black_pieces())
+        game.board.black_king
     };
     if my_king == 0 {
         return;
     }
     let from = my_king.trailing_zeros() as usize;
     let mut attacks = game.board.get_king_attacks(from);
-    attacks &= !my_pieces;
+    attacks &= targets;
     while attacks != 0 {
         let to = attacks.trailing_zeros() as usize;
         moves.add(from, to, None);
         attacks &= attacks - 1;
     }
+    if !castling {
+        return;
+    }
+    let castles = if game.is_white_turn { [6, 2] } else { [62, 58] };
+    for to in castles {
+        if can_castle(game, to) {
+            moves.add(from, to, None);
+        }
+    }
+}
+
+/// Whether the side to move may castle with its king ending on `to`, checked the same way
+/// for generated moves and for moves from elsewhere.
+fn can_castle(game: &Game, to: usize) -> bool {
     let all = game.board.all_pieces();
-    if game.is_white_turn {
-        if (game.castling & 0b1000) != 0
-            && (all & 0x60) == 0
-            && !game.board.possible_check(4, false)
-            && !game.board.possible_check(5, false)
-        {
-            moves.add(4, 6, None);
-        }
-        if (game.castling & 0b0100) != 0
-            && (all & 0xE) == 0
-            && !game.board.possible_check(4, false)
-            && !game.board.possible_check(3, false)
-        {
-            moves.add(4, 2, None);
+    match (game.is_white_turn, to) {
+        (true, 6) => {
+            (game.castling & 0b1000) != 0
+                && (all & 0x60) == 0
+                && !game.board.possible_check(4, false)
+                && !game.board.possible_check(5, false)
         }
-    } else {
-        if (game.castling & 0b0010) != 0
-            && (all & 0x6000000000000000) == 0
-            && !game.board.possible_check(60, true)
-            && !game.board.possible_check(61, true)
-        {
-            moves.add(60, 62, None);
-        }
-        if (game.castling & 0b0001) != 0
-            && (all & 0xE00000000000000) == 0
-            && !game.board.possible_check(60, true)
-            && !game.board.possible_check(59, true)
-        {
-            moves.add(60, 58, None);
+        (true, 2) => {
+            (game.castling & 0b0100) != 0
+                && (all & 0xE) == 0
+                && !game.board.possible_check(4, false)
+                && !game.board.possible_check(3, false)
+        }
+        (false, 62) => {
+            (game.castling & 0b0010) != 0
+                && (all & 0x6000000000000000) == 0
+                && !game.board.possible_check(60, true)
+                && !game.board.possible_check(61, true)
+        }
+        (false, 58) => {
+            (game.castling & 0b0001) != 0
+                && (all & 0xE00000000000000) == 0
+                && !game.board.possible_check(60, true)
+                && !game.board.possible_check(59, true)
         }
+        _ => false,
     }
 }
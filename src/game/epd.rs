@@ -0,0 +1,192 @@
+use std::fmt;
+
+use super::fen::FenError;
+use super::san::SanError;
+use crate::{Game, Piece};
+
+type Move = (usize, usize, Option<Piece>);
+
+/// Why an EPD record could not be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EpdError {
+    /// The four position fields do not describe a valid position.
+    InvalidPosition(FenError),
+    /// A quoted operand without its closing quote.
+    UnterminatedString,
+    /// An operand that does not fit its opcode, e.g. a negative `acd`.
+    BadOperand { opcode: String, operand: String },
+    /// A `bm` or `am` move that does not resolve in the position.
+    BadMove { opcode: String, error: SanError },
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpdError::InvalidPosition(e) => write!(f, "invalid position: {e}"),
+            EpdError::UnterminatedString => write!(f, "string operand is missing its closing '\"'"),
+            EpdError::BadOperand { opcode, operand } => {
+                write!(f, "invalid operand '{operand}' for {opcode}")
+            }
+            EpdError::BadMove { opcode, error } => write!(f, "{opcode}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+impl From<FenError> for EpdError {
+    fn from(e: FenError) -> Self {
+        EpdError::InvalidPosition(e)
+    }
+}
+
+/// One EPD record: a position followed by `opcode operand...;` operations.
+///
+/// The opcodes the test suites rely on are decoded into their own fields, every
+/// operation is also kept as written in `operations`.
+#[derive(Clone)]
+pub struct Epd {
+    pub game: Game,
+    /// Best moves, any of them solves the position.
+    pub best_moves: Vec<Move>,
+    /// Moves to avoid, none of them may be played.
+    pub avoid_moves: Vec<Move>,
+    pub id: Option<String>,
+    /// The primary comment, `c0`.
+    pub comment: Option<String>,
+    /// Direct mate in this many moves.
+    pub direct_mate: Option<u32>,
+    /// Depth the position was analysed to, `acd`.
+    pub analysis_depth: Option<u32>,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    /// Parses a single EPD line. The `hmvc` and `fmvn` opcodes set the move counters, which
+    /// otherwise take their defaults as the position fields carry no clocks.
+    pub fn parse(line: &str) -> Result<Self, EpdError> {
+        let line = line.trim();
+        // The four position fields, however much whitespace separates them, then the operations
+        let mut position = Vec::with_capacity(4);
+        let mut rest = line;
+        while position.len() < 4 && !rest.is_empty() {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            position.push(field);
+            rest = tail.trim_start();
+        }
+        let game = Game::from_fen(&position.join(" "))?;
+        let operations = parse_operations(rest)?;
+
+        let mut halfmove_clock = game.halfmove_clock;
+        let mut fullmove_number = game.fullmove_number;
+        let mut epd = Epd {
+            game,
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+            id: None,
+            comment: None,
+            direct_mate: None,
+            analysis_depth: None,
+            operations: Vec::new(),
+        };
+
+        for (opcode, operands) in &operations {
+            let number = || -> Result<u32, EpdError> {
+                operands
+                    .first()
+                    .and_then(|operand| operand.parse().ok())
+                    .ok_or_else(|| EpdError::BadOperand {
+                        opcode: opcode.clone(),
+                        operand: operands.join(" "),
+                    })
+            };
+            let moves = || -> Result<Vec<Move>, EpdError> {
+                operands
+                    .iter()
+                    .map(|san| {
+                        epd.game.parse_san(san).map_err(|error| EpdError::BadMove {
+                            opcode: opcode.clone(),
+                            error,
+                        })
+                    })
+                    .collect()
+            };
+
+            match opcode.as_str() {
+                "bm" => epd.best_moves = moves()?,
+                "am" => epd.avoid_moves = moves()?,
+                "id" => epd.id = operands.first().cloned(),
+                "c0" => epd.comment = operands.first().cloned(),
+                "dm" => epd.direct_mate = Some(number()?),
+                "acd" => epd.analysis_depth = Some(number()?),
+                "hmvc" => halfmove_clock = number()?,
+                "fmvn" => fullmove_number = number()?,
+                _ => {}
+            }
+        }
+
+        epd.game.halfmove_clock = halfmove_clock;
+        epd.game.fullmove_number = fullmove_number;
+        epd.operations = operations;
+        Ok(epd)
+    }
+
+    /// All records in `text`, skipping blank lines and `#` comments.
+    pub fn parse_all(text: &str) -> Result<Vec<Self>, EpdError> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Self::parse)
+            .collect()
+    }
+
+    /// The operands of the first operation with this opcode.
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+}
+
+/// Splits `bm Nf3 Qd2; id "WAC.001";` into opcodes and their operands. Quoted operands
+/// keep their spaces and semicolons.
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            None | Some(';') => {
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
+                if chars.peek().is_none() {
+                    break;
+                }
+            }
+            Some('"') => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(EpdError::UnterminatedString),
+                    }
+                }
+                words.push(word);
+            }
+            Some(c) => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+                    word.push(c);
+                }
+                words.push(word);
+            }
+        }
+    }
+    Ok(operations)
+}
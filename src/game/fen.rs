@@ -1,129 +1,383 @@
-use std::collections::HashMap; // Ensure HashMap is in scope if not already.
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::sync::Arc;
 
+use crate::search::pst::get_piece_colour_at;
 use crate::search::tt::TranspositionTable;
-use crate::{Bitboard, Game};
+use crate::search::zobrist::compute_zobrist_hash;
+use crate::{Bitboard, Game, Piece};
 
-impl Game {
-    pub fn from_fen(fen: &str) -> Result<Self, &'static str> {
-        let mut board = Bitboard::empty(); // Start with an empty board
-        let parts: Vec<&str> = fen.split_whitespace().collect();
-        if parts.len() < 4 {
-            return Err("Invalid FEN string: not enough parts.");
-        }
+/// How forgiving `Game::from_fen_with_mode` is with FENs that are well-formed but off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FenMode {
+    /// All six fields are required and every field must agree with the position.
+    Strict,
+    /// Missing fields get their defaults, and castling rights or an en passant square
+    /// that cannot apply to the position are dropped instead of rejected.
+    Lenient,
+}
 
-        // Pieces on le board
-        let piece_placement = parts[0];
-        let mut rank = 7;
-        let mut file = 0;
-        for ch in piece_placement.chars() {
-            if ch == '/' {
-                rank -= 1;
-                file = 0;
-            } else if let Some(digit) = ch.to_digit(10) {
-                file += digit as i32;
-            } else {
-                if file > 7 {
-                    return Err("Invalid FEN: file out of bounds");
-                }
-                let square_index = (rank * 8 + file) as usize;
-                match ch {
-                    'P' => {
-                        board.white_pawns |= 1 << square_index;
-                    }
-                    'N' => {
-                        board.white_knight |= 1 << square_index;
-                    }
-                    'B' => {
-                        board.white_bishop |= 1 << square_index;
-                    }
-                    'R' => {
-                        board.white_rook |= 1 << square_index;
-                    }
-                    'Q' => {
-                        board.white_queen |= 1 << square_index;
-                    }
-                    'K' => {
-                        board.white_king |= 1 << square_index;
-                    }
-                    'p' => {
-                        board.black_pawns |= 1 << square_index;
-                    }
-                    'n' => {
-                        board.black_knight |= 1 << square_index;
-                    }
-                    'b' => {
-                        board.black_bishop |= 1 << square_index;
-                    }
-                    'r' => {
-                        board.black_rook |= 1 << square_index;
-                    }
-                    'q' => {
-                        board.black_queen |= 1 << square_index;
-                    }
-                    'k' => {
-                        board.black_king |= 1 << square_index;
-                    }
-                    _ => return Err("Invalid character in FEN piece placement."),
-                };
-                file += 1;
+/// Why a FEN could not be parsed. Squares and characters tell where the problem is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    MissingField(&'static str),
+    /// A character in the piece placement that is neither a piece, a digit nor '/'.
+    InvalidPiece { character: char, index: usize },
+    /// A rank describing more or fewer than eight squares, ranks counted from 8 down.
+    BadRankLength { rank: usize },
+    WrongRankCount(usize),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    KingCount { white: u32, black: u32 },
+    PawnOnBackRank { square: usize },
+    /// The side that just moved has its king in check.
+    OpponentInCheck,
+    /// A castling right without the king and rook on their starting squares.
+    CastlingWithoutPieces(char),
+    /// An en passant square no double pawn push can have produced.
+    ImpossibleEnPassant { square: usize },
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "missing {field} field"),
+            FenError::InvalidPiece { character, index } => {
+                write!(f, "invalid piece '{character}' at character {}", index + 1)
+            }
+            FenError::BadRankLength { rank } => {
+                write!(f, "rank {rank} does not describe exactly 8 squares")
+            }
+            FenError::WrongRankCount(count) => write!(f, "expected 8 ranks, found {count}"),
+            FenError::InvalidSideToMove(side) => write!(f, "invalid side to move '{side}'"),
+            FenError::InvalidCastling(castling) => {
+                write!(f, "invalid castling rights '{castling}'")
+            }
+            FenError::InvalidEnPassant(square) => {
+                write!(f, "invalid en passant square '{square}'")
+            }
+            FenError::InvalidHalfmoveClock(clock) => {
+                write!(f, "invalid halfmove clock '{clock}'")
+            }
+            FenError::InvalidFullmoveNumber(number) => {
+                write!(f, "invalid fullmove number '{number}'")
+            }
+            FenError::KingCount { white, black } => write!(
+                f,
+                "expected one king per side, found {white} white and {black} black"
+            ),
+            FenError::PawnOnBackRank { square } => {
+                write!(f, "pawn on back rank square {}", square_name(*square))
+            }
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            FenError::CastlingWithoutPieces(right) => write!(
+                f,
+                "castling right '{right}' without king and rook on their starting squares"
+            ),
+            FenError::ImpossibleEnPassant { square } => {
+                write!(f, "impossible en passant square {}", square_name(*square))
             }
         }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+fn square_name(square: usize) -> String {
+    let file = (b'a' + (square % 8) as u8) as char;
+    let rank = (b'1' + (square / 8) as u8) as char;
+    format!("{file}{rank}")
+}
+
+// (castling right, bit, king square, rook square)
+const CASTLING_RIGHTS: [(char, u8, usize, usize); 4] = [
+    ('K', 3, 4, 7),
+    ('Q', 2, 4, 0),
+    ('k', 1, 60, 63),
+    ('q', 0, 60, 56),
+];
+
+impl Game {
+    /// Parses a FEN leniently, see [`FenMode::Lenient`].
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        Self::from_fen_with_mode(fen, FenMode::Lenient)
+    }
+
+    pub fn from_fen_with_mode(fen: &str, mode: FenMode) -> Result<Self, FenError> {
+        let strict = mode == FenMode::Strict;
+        let parts: Vec<&str> = fen.split_whitespace().collect();
+        let field = |index: usize, name: &'static str, default: &'static str| {
+            match parts.get(index) {
+                Some(&part) => Ok(part),
+                None if strict || index < 2 => Err(FenError::MissingField(name)),
+                None => Ok(default),
+            }
+        };
+
+        let board = parse_piece_placement(field(0, "piece placement", "")?)?;
 
         // Colour time
-        let is_white_turn = match parts[1] {
+        let is_white_turn = match field(1, "side to move", "")? {
             "w" => true,
             "b" => false,
-            _ => return Err("Invalid active colour in FEN."),
+            side => return Err(FenError::InvalidSideToMove(side.to_string())),
         };
 
         // Parse castling rights
         let mut castling = 0u8;
-        let castling_rights = parts[2];
-        if castling_rights.contains('K') {
-            castling |= 1 << 3;
-        }
-        if castling_rights.contains('Q') {
-            castling |= 1 << 2;
-        }
-        if castling_rights.contains('k') {
-            castling |= 1 << 1;
-        }
-        if castling_rights.contains('q') {
-            castling |= 1 << 0;
+        let castling_rights = field(2, "castling", "-")?;
+        if castling_rights != "-" {
+            for ch in castling_rights.chars() {
+                let Some(&(_, bit, _, _)) = CASTLING_RIGHTS.iter().find(|r| r.0 == ch) else {
+                    return Err(FenError::InvalidCastling(castling_rights.to_string()));
+                };
+                castling |= 1 << bit;
+            }
         }
 
         // if en passent is available
-        let en_passent_str = parts[3];
+        let en_passent_str = field(3, "en passant", "-")?;
         let en_passent = if en_passent_str == "-" {
             None
         } else {
-            let file = en_passent_str
-                .chars()
-                .nth(0)
-                .ok_or("Invalid en passant square")? as u8
-                - b'a';
-            let rank = en_passent_str
-                .chars()
-                .nth(1)
-                .ok_or("Invalid en passant square")? as u8
-                - b'1';
-            if file > 7 || rank > 7 {
-                return Err("Invalid en passant square");
-            }
-            Some((rank * 8 + file) as usize)
+            let bytes = en_passent_str.as_bytes();
+            if bytes.len() != 2
+                || !(b'a'..=b'h').contains(&bytes[0])
+                || !(b'1'..=b'8').contains(&bytes[1])
+            {
+                return Err(FenError::InvalidEnPassant(en_passent_str.to_string()));
+            }
+            Some(((bytes[1] - b'1') * 8 + (bytes[0] - b'a')) as usize)
         };
 
-        // Ignore halfmove and full_move clos
+        let halfmove_str = field(4, "halfmove clock", "0")?;
+        let halfmove_clock = match halfmove_str.parse::<u32>() {
+            Ok(clock) => clock,
+            Err(_) if !strict => 0,
+            Err(_) => return Err(FenError::InvalidHalfmoveClock(halfmove_str.to_string())),
+        };
+        let fullmove_str = field(5, "fullmove number", "1")?;
+        let fullmove_number = match fullmove_str.parse::<u32>() {
+            Ok(number) if number > 0 => number,
+            _ if !strict => 1,
+            _ => return Err(FenError::InvalidFullmoveNumber(fullmove_str.to_string())),
+        };
 
-        Ok(Game {
+        let mut game = Game {
             board,
             is_white_turn,
             castling,
             en_passent,
-            position_history: HashMap::new(),
-            tt: Arc::new(Mutex::new(TranspositionTable::new())),
+            position_history: Vec::new(),
+            halfmove_clock,
+            plies_since_null: 0,
+            fullmove_number,
+            tt: Arc::new(TranspositionTable::new()),
             zobrist_hash: 0,
-        })
+        };
+        game.validate(strict)?;
+        game.zobrist_hash = compute_zobrist_hash(game.clone());
+        Ok(game)
+    }
+
+    /// Rejects positions the engine cannot play from, and in strict mode castling
+    /// rights or an en passant square that contradict the board. Lenient mode drops those.
+    fn validate(&mut self, strict: bool) -> Result<(), FenError> {
+        let board = &self.board;
+        let white = board.white_king.count_ones();
+        let black = board.black_king.count_ones();
+        if white != 1 || black != 1 {
+            return Err(FenError::KingCount { white, black });
+        }
+
+        const BACK_RANKS: u64 = 0xFF00_0000_0000_00FF;
+        let pawns_on_back_rank = (board.white_pawns | board.black_pawns) & BACK_RANKS;
+        if pawns_on_back_rank != 0 {
+            return Err(FenError::PawnOnBackRank {
+                square: pawns_on_back_rank.trailing_zeros() as usize,
+            });
+        }
+
+        let opponent_king = if self.is_white_turn {
+            board.black_king
+        } else {
+            board.white_king
+        };
+        if board.possible_check(opponent_king.trailing_zeros() as usize, self.is_white_turn) {
+            return Err(FenError::OpponentInCheck);
+        }
+
+        for &(right, bit, king_square, rook_square) in &CASTLING_RIGHTS {
+            let (king, rook) = if right.is_ascii_uppercase() {
+                (board.white_king, board.white_rook)
+            } else {
+                (board.black_king, board.black_rook)
+            };
+            let in_place = king & (1 << king_square) != 0 && rook & (1 << rook_square) != 0;
+            if self.castling & (1 << bit) != 0 && !in_place {
+                if strict {
+                    return Err(FenError::CastlingWithoutPieces(right));
+                }
+                self.castling &= !(1 << bit);
+            }
+        }
+
+        if let Some(square) = self.en_passent
+            && !self.en_passant_possible(square)
+        {
+            if strict {
+                return Err(FenError::ImpossibleEnPassant { square });
+            }
+            self.en_passent = None;
+        }
+
+        Ok(())
+    }
+
+    /// True when a pawn of the side that just moved can have double pushed over `square`.
+    fn en_passant_possible(&self, square: usize) -> bool {
+        let board = &self.board;
+        let expected_rank = if self.is_white_turn { 5 } else { 2 };
+        if square / 8 != expected_rank {
+            return false;
+        }
+
+        // The pushed pawn stands one rank past the square, its origin one rank before it
+        let (pawn_square, origin, pawns) = if self.is_white_turn {
+            (square - 8, square + 8, board.black_pawns)
+        } else {
+            (square + 8, square - 8, board.white_pawns)
+        };
+        pawns & (1 << pawn_square) != 0 && board.all_pieces() & (1 << square | 1 << origin) == 0
+    }
+}
+
+/// Parses the first FEN field into a board, ranks run from 8 down to 1.
+fn parse_piece_placement(placement: &str) -> Result<Bitboard, FenError> {
+    let mut board = Bitboard::empty(); // Start with an empty board
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::WrongRankCount(ranks.len()));
+    }
+
+    let mut index = 0;
+    for (rank_from_top, rank_str) in ranks.iter().enumerate() {
+        let rank = 7 - rank_from_top;
+        let mut file = 0;
+        for ch in rank_str.chars() {
+            if let Some(digit) = ch.to_digit(10).filter(|d| (1..=8).contains(d)) {
+                file += digit as usize;
+            } else {
+                let Some(piece) = piece_from_char(ch) else {
+                    return Err(FenError::InvalidPiece { character: ch, index });
+                };
+                if file > 7 {
+                    return Err(FenError::BadRankLength { rank: rank + 1 });
+                }
+                *board.get_mut_board(piece, ch.is_ascii_uppercase()) |= 1 << (rank * 8 + file);
+                file += 1;
+            }
+            if file > 8 {
+                return Err(FenError::BadRankLength { rank: rank + 1 });
+            }
+            index += ch.len_utf8();
+        }
+        if file != 8 {
+            return Err(FenError::BadRankLength { rank: rank + 1 });
+        }
+        index += 1; // the '/'
+    }
+
+    Ok(board)
+}
+
+fn piece_from_char(ch: char) -> Option<Piece> {
+    match ch.to_ascii_lowercase() {
+        'p' => Some(Piece::Pawn),
+        'n' => Some(Piece::Knight),
+        'b' => Some(Piece::Bishop),
+        'r' => Some(Piece::Rook),
+        'q' => Some(Piece::Queen),
+        'k' => Some(Piece::King),
+        _ => None,
+    }
+}
+
+/// Formats a game as FEN, obtained from [`Game::fen`].
+pub struct Fen<'a>(&'a Game);
+
+impl fmt::Display for Fen<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let game = self.0;
+
+        // Pieces, from rank 8 down to rank 1
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match get_piece_colour_at(game, rank * 8 + file) {
+                    Some((piece, is_white)) => {
+                        if empty > 0 {
+                            write!(f, "{empty}")?;
+                            empty = 0;
+                        }
+                        let ch = match piece {
+                            Piece::Pawn => 'p',
+                            Piece::Knight => 'n',
+                            Piece::Bishop => 'b',
+                            Piece::Rook => 'r',
+                            Piece::Queen => 'q',
+                            Piece::King => 'k',
+                        };
+                        let ch = if is_white { ch.to_ascii_uppercase() } else { ch };
+                        write!(f, "{ch}")?;
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                write!(f, "{empty}")?;
+            }
+            if rank > 0 {
+                write!(f, "/")?;
+            }
+        }
+
+        write!(f, " {}", if game.is_white_turn { 'w' } else { 'b' })?;
+
+        // Only the low four bits are castling rights, the rest is bookkeeping for `Game::run`
+        let mut castling = String::new();
+        for (bit, ch) in [(3, 'K'), (2, 'Q'), (1, 'k'), (0, 'q')] {
+            if game.castling & (1 << bit) != 0 {
+                castling.push(ch);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        write!(f, " {castling}")?;
+
+        match game.en_passent {
+            Some(square) => {
+                let file = (b'a' + (square % 8) as u8) as char;
+                let rank = (b'1' + (square / 8) as u8) as char;
+                write!(f, " {file}{rank}")?;
+            }
+            None => write!(f, " -")?,
+        }
+
+        write!(f, " {} {}", game.halfmove_clock, game.fullmove_number)
+    }
+}
+
+impl Game {
+    /// Returns a FEN formatter for this position, e.g. for `println!("{}", game.fen())`.
+    pub fn fen(&self) -> Fen<'_> {
+        Fen(self)
+    }
+
+    pub fn to_fen(&self) -> String {
+        self.fen().to_string()
     }
 }
@@ -1,5 +1,8 @@
+pub mod epd;
 pub mod fen;
 pub mod perft;
+pub mod pgn;
+pub mod san;
 
 use crate::board::Bitboard;
 use crate::board::display::print_board;
@@ -9,13 +12,8 @@ use crate::search::tt::TranspositionTable;
 use crate::search::zobrist::{ZobristKeys, compute_zobrist_hash};
 use crate::{MoveList, Piece}; // Import Bitboard from the appropriate module
 use once_cell::sync::Lazy;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-use std::sync::{Arc, Mutex};
-use std::{
-    collections::HashMap,
-    io::{self, Write},
-};
+use std::io::{self, Write};
+use std::sync::Arc;
 
 pub static ZOBRIST_KEYS: Lazy<ZobristKeys> = Lazy::new(ZobristKeys::new);
 
@@ -25,8 +23,11 @@ pub struct Game {
     pub is_white_turn: bool,
     pub castling: u8, // This will be represented with a 8 digit binary
     pub en_passent: Option<usize>,
-    pub position_history: HashMap<u64, u32>, // Essentially, en_passent moves are pushed onto the vec and popped off after 1 turn
-    pub tt: Arc<Mutex<TranspositionTable>>,
+    pub position_history: Vec<u64>, // Zobrist keys of every earlier position, pushed by make and popped by unmake
+    pub halfmove_clock: u32, // Plies since the last capture or pawn move, for the fifty-move rule
+    pub plies_since_null: u32, // Plies since the last null move, no repetition reaches across one
+    pub fullmove_number: u32, // Starts at 1 and goes up after every black move
+    pub tt: Arc<TranspositionTable>,
     pub zobrist_hash: u64,
 }
 #[derive(Clone)]
@@ -38,6 +39,17 @@ pub struct Undo {
     pub previous_castling_rights: u8,
     pub previous_en_passant_square: Option<usize>,
     pub previous_zobrist_hash: u64,
+    pub previous_halfmove_clock: u32,
+    pub previous_plies_since_null: u32,
+    pub previous_fullmove_number: u32,
+}
+
+/// What `make_null_move` changed, so `unmake_null_move` can restore it.
+#[derive(Debug, Clone, Copy)]
+pub struct NullMoveUndo {
+    pub previous_en_passant_square: Option<usize>,
+    pub previous_zobrist_hash: u64,
+    pub previous_plies_since_null: u32,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -47,6 +59,7 @@ pub enum GameState {
     Checkmate,
     Stalemate,
     RepMoves,
+    FiftyMoves,
 }
 
 impl Default for Game {
@@ -56,8 +69,11 @@ impl Default for Game {
             is_white_turn: true,
             castling: 0b11111111,
             en_passent: None,
-            position_history: HashMap::new(),
-            tt: Arc::new(Mutex::new(TranspositionTable::new())),
+            position_history: Vec::new(),
+            halfmove_clock: 0,
+            plies_since_null: 0,
+            fullmove_number: 1,
+            tt: Arc::new(TranspositionTable::new()),
             zobrist_hash: 0,
         };
         let mut game = game;
@@ -81,6 +97,9 @@ impl Game {
         let en_passent_available = self.en_passent;
         self.en_passent = None;
 
+        let is_pawn_move = (self.board.white_pawns | self.board.black_pawns) & from_mask != 0;
+        let is_capture = self.board.all_pieces() & (1u64 << to) != 0;
+
         if promo.is_some() && (self.board.white_pawns | self.board.black_pawns) & from_mask == 0 {
             return false;
         }
@@ -136,6 +155,15 @@ impl Game {
             return false;
         }
 
+        if is_pawn_move || is_capture {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if !moving_side {
+            self.fullmove_number += 1;
+        }
+
         self.is_white_turn = !self.is_white_turn;
         true
     }
@@ -253,6 +281,11 @@ impl Game {
         let is_in_check = self.is_in_check();
         let has_legal_moves = !self.generate_legal_moves().is_empty();
 
+        // Checkmate on the hundredth ply still counts, everything else is a draw.
+        if self.is_fifty_move_draw() && (has_legal_moves || !is_in_check) {
+            return GameState::FiftyMoves;
+        }
+
         if is_in_check {
             if has_legal_moves {
                 if self.is_white_turn {
@@ -300,11 +333,23 @@ impl Game {
             previous_castling_rights: previous_castling,
             previous_en_passant_square: previous_en_passant,
             previous_zobrist_hash: previous_hash,
+            previous_halfmove_clock: self.halfmove_clock,
+            previous_plies_since_null: self.plies_since_null,
+            previous_fullmove_number: self.fullmove_number,
         };
 
-        // === Zobrist Unhash Old State ===
+        self.position_history.push(previous_hash);
+        self.plies_since_null += 1;
+        if piece_moving == Piece::Pawn || captured_piece.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if !self.is_white_turn {
+            self.fullmove_number += 1;
+        }
 
-        self.zobrist_hash ^= ZOBRIST_KEYS.side_to_move_key;
+        // === Zobrist Unhash Old State ===
 
         if let Some(ep_sq) = previous_en_passant {
             let file = ep_sq % 8;
@@ -356,14 +401,27 @@ impl Game {
         // === Update castling rights on rook capture ===
         if let Some(Piece::Rook) = captured_piece {
             match to {
-                0 => self.castling &= !0b0001,  // a8
-                7 => self.castling &= !0b0010,  // h8
-                56 => self.castling &= !0b0100, // a1
-                63 => self.castling &= !0b1000, // h1
+                0 => self.castling &= !0b0100,  // a1
+                7 => self.castling &= !0b1000,  // h1
+                56 => self.castling &= !0b0001, // a8
+                63 => self.castling &= !0b0010, // h8
                 _ => {}
             }
         }
 
+        // The rook moves along with a castling king
+        if piece_moving == Piece::King && (from as i8 - to as i8).abs() == 2 {
+            let (rook_from, rook_to) = match to {
+                6 => (7, 5),
+                2 => (0, 3),
+                62 => (63, 61),
+                58 => (56, 59),
+                _ => unreachable!("A king move of 2 squares must be a castle."),
+            };
+            self.zobrist_hash ^= ZOBRIST_KEYS.piece_keys[side][Piece::Rook as usize][rook_from];
+            self.zobrist_hash ^= ZOBRIST_KEYS.piece_keys[side][Piece::Rook as usize][rook_to];
+        }
+
         // === Zobrist Re-hash New State ===
 
         if let Some(ep_sq) = self.en_passent {
@@ -384,6 +442,53 @@ impl Game {
         undo
     }
 
+    /// Passes the turn without moving a piece, as used by null-move pruning.
+    pub fn make_null_move(&mut self) -> NullMoveUndo {
+        let undo = NullMoveUndo {
+            previous_en_passant_square: self.en_passent,
+            previous_zobrist_hash: self.zobrist_hash,
+            previous_plies_since_null: self.plies_since_null,
+        };
+
+        // The halfmove clock goes on, but repetition checks must not look past the null move.
+        self.position_history.push(self.zobrist_hash);
+        self.plies_since_null = 0;
+
+        if let Some(ep_sq) = self.en_passent.take() {
+            self.zobrist_hash ^= ZOBRIST_KEYS.en_passent_keys[ep_sq % 8];
+        }
+        self.is_white_turn = !self.is_white_turn;
+        self.zobrist_hash ^= ZOBRIST_KEYS.side_to_move_key;
+
+        undo
+    }
+
+    pub fn unmake_null_move(&mut self, undo: NullMoveUndo) {
+        self.is_white_turn = !self.is_white_turn;
+        self.en_passent = undo.previous_en_passant_square;
+        self.zobrist_hash = undo.previous_zobrist_hash;
+        self.plies_since_null = undo.previous_plies_since_null;
+        self.position_history.pop();
+    }
+
+    /// Counts how often the current position occurred before. Only positions since the
+    /// last capture, pawn move or null move with the same side to move can be equal.
+    pub fn repetition_count(&self) -> usize {
+        self.position_history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock.min(self.plies_since_null) as usize)
+            .skip(1)
+            .step_by(2)
+            .filter(|&&key| key == self.zobrist_hash)
+            .count()
+    }
+
+    /// True once fifty moves by each side passed without a capture or pawn move.
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
+
     pub fn unmake_move(&mut self, undo: Undo) {
         self.is_white_turn = !self.is_white_turn;
         self.castling = undo.previous_castling_rights;
@@ -439,6 +544,10 @@ impl Game {
 
         // Restore prev zobrist hash
         self.zobrist_hash = undo.previous_zobrist_hash;
+        self.halfmove_clock = undo.previous_halfmove_clock;
+        self.plies_since_null = undo.previous_plies_since_null;
+        self.fullmove_number = undo.previous_fullmove_number;
+        self.position_history.pop();
     }
     fn get_piece_bb_mut(&mut self, piece: Piece, is_white: bool) -> &mut u64 {
         match (is_white, piece) {
@@ -474,16 +583,6 @@ impl Game {
         *bitboard &= !(1u64 << square);
     }
 
-    pub fn hash_position(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.board.all_pieces().hash(&mut hasher);
-        self.is_white_turn.hash(&mut hasher);
-        self.castling.hash(&mut hasher);
-        self.en_passent.hash(&mut hasher);
-
-        hasher.finish()
-    }
-
     pub fn parse_move(input: &str) -> Option<(usize, usize, Option<Piece>)> {
         if input.len() != 4 && input.len() != 5 {
             return None;
@@ -542,6 +641,10 @@ impl Game {
                     println!("\n*** 3 moves in a row! The game is a draw. ***");
                     break;
                 }
+                GameState::FiftyMoves => {
+                    println!("\n*** 50 moves without a capture or pawn move! The game is a draw. ***");
+                    break;
+                }
                 GameState::Check => {
                     println!("\n*** You are in CHECK! ***");
                 }
@@ -565,14 +668,11 @@ impl Game {
 
             match Self::parse_move(trimmed_input) {
                 Some((from, to, promo)) => {
-                    if self.make_move(from, to, promo) {
-                        // If a move is possible, add it to the move history list
-                        let position_hash = self.hash_position();
-
-                        let count = self.position_history.entry(position_hash).or_insert(0);
-                        *count += 1;
+                    if self.generate_legal_moves().iter().any(|m| *m == (from, to, promo)) {
+                        // The unchecked move keeps the hash and position history up to date
+                        self.make_move_unchecked(from, to, promo);
 
-                        if *count == 3 {
+                        if self.repetition_count() >= 2 {
                             println!("\n*** 3 moves in a row! The game is a draw. ***");
                             break;
                         }
@@ -0,0 +1,520 @@
+use std::fmt;
+
+use super::fen::FenError;
+use super::san::SanError;
+use crate::{Game, Piece};
+
+type Move = (usize, usize, Option<Piece>);
+
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Export format keeps movetext lines below this many characters.
+const LINE_WIDTH: usize = 80;
+
+/// Why a PGN could not be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnError {
+    /// A `[...]` tag pair that is not of the form `[Name "value"]`.
+    BadTag(String),
+    UnterminatedComment,
+    /// The FEN tag does not describe a valid position.
+    InvalidFen(FenError),
+    /// A move that does not resolve in the position it is played from.
+    BadMove {
+        move_number: u32,
+        san: String,
+        error: SanError,
+    },
+    /// A token that cannot appear where it was found, e.g. a `)` without a matching `(`.
+    UnexpectedToken(String),
+    UnterminatedVariation,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::BadTag(tag) => write!(f, "malformed tag pair '{tag}'"),
+            PgnError::UnterminatedComment => write!(f, "comment is missing its closing '}}'"),
+            PgnError::InvalidFen(e) => write!(f, "invalid FEN tag: {e}"),
+            PgnError::BadMove {
+                move_number,
+                san,
+                error,
+            } => write!(f, "move {move_number} ({san}): {error}"),
+            PgnError::UnexpectedToken(token) => write!(f, "unexpected '{token}' in movetext"),
+            PgnError::UnterminatedVariation => write!(f, "variation is missing its closing ')'"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl From<FenError> for PgnError {
+    fn from(e: FenError) -> Self {
+        PgnError::InvalidFen(e)
+    }
+}
+
+/// One move of the movetext together with everything annotating it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PgnMove {
+    pub mv: Move,
+    /// Numeric annotation glyphs, `!` and `?` style suffixes are read as their NAG equivalents.
+    pub nags: Vec<u8>,
+    pub comment_before: Option<String>,
+    pub comment: Option<String>,
+    /// Alternatives to this move, each played from the position before it.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(mv: Move) -> Self {
+        Self {
+            mv,
+            ..Self::default()
+        }
+    }
+}
+
+/// A single game: its tag pairs, the starting position and the annotated main line.
+#[derive(Clone)]
+pub struct PgnGame {
+    /// Tag pairs in the order they are written, starting with the seven tag roster.
+    pub tags: Vec<(String, String)>,
+    pub start: Game,
+    pub moves: Vec<PgnMove>,
+    /// `1-0`, `0-1`, `1/2-1/2` or `*` for an unfinished game.
+    pub result: String,
+}
+
+impl PgnGame {
+    /// An empty game from `start`, with the seven tag roster filled with unknowns and the
+    /// SetUp and FEN tags added when `start` is not the standard starting position.
+    pub fn new(start: &Game) -> Self {
+        let mut tags: Vec<(String, String)> = [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", "*"),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+        let fen = start.to_fen();
+        if fen != STARTING_FEN {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen));
+        }
+
+        Self {
+            tags,
+            start: start.clone(),
+            moves: Vec::new(),
+            result: "*".to_string(),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value of a tag, adding it at the end if it is not present yet.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Sets the game termination marker and the matching Result tag.
+    pub fn set_result(&mut self, result: &str) {
+        self.result = result.to_string();
+        self.set_tag("Result", result);
+    }
+
+    /// Appends a move to the main line, returns false and leaves the game alone if it is not legal.
+    pub fn push(&mut self, mv: Move) -> bool {
+        if !self.final_position().generate_legal_moves().iter().any(|m| *m == mv) {
+            return false;
+        }
+        self.moves.push(PgnMove::new(mv));
+        true
+    }
+
+    /// The starting position followed by the position after every main line move.
+    pub fn positions(&self) -> Vec<Game> {
+        let mut positions = Vec::with_capacity(self.moves.len() + 1);
+        let mut game = self.start.clone();
+        for m in &self.moves {
+            positions.push(game.clone());
+            game.make_move_unchecked(m.mv.0, m.mv.1, m.mv.2);
+        }
+        positions.push(game);
+        positions
+    }
+
+    pub fn final_position(&self) -> Game {
+        let mut game = self.start.clone();
+        for m in &self.moves {
+            game.make_move_unchecked(m.mv.0, m.mv.1, m.mv.2);
+        }
+        game
+    }
+}
+
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{name} \"{value}\"]")?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        write_moves(&mut tokens, &self.moves, &self.start);
+        tokens.push(self.result.clone());
+
+        // Brackets hug the variation they enclose, everything else is space separated.
+        let mut line = String::new();
+        let mut previous = "";
+        for token in &tokens {
+            let glue = !line.is_empty() && previous != "(" && token != ")";
+            if glue && line.len() + 1 + token.len() > LINE_WIDTH {
+                writeln!(f, "{line}")?;
+                line.clear();
+            } else if glue {
+                line.push(' ');
+            }
+            line.push_str(token);
+            previous = token;
+        }
+        writeln!(f, "{line}")
+    }
+}
+
+/// Turns `moves`, played from `position`, into movetext tokens.
+fn write_moves(tokens: &mut Vec<String>, moves: &[PgnMove], position: &Game) {
+    let mut game = position.clone();
+    // Black moves need their number repeated whenever something interrupts the move pair.
+    let mut interrupted = true;
+
+    for m in moves {
+        if let Some(comment) = &m.comment_before {
+            tokens.push(format!("{{{comment}}}"));
+            interrupted = true;
+        }
+        if game.is_white_turn {
+            tokens.push(format!("{}.", game.fullmove_number));
+        } else if interrupted {
+            tokens.push(format!("{}...", game.fullmove_number));
+        }
+        tokens.push(game.move_to_san(m.mv));
+        tokens.extend(m.nags.iter().map(|nag| format!("${nag}")));
+        interrupted = false;
+
+        if let Some(comment) = &m.comment {
+            tokens.push(format!("{{{comment}}}"));
+            interrupted = true;
+        }
+        for variation in &m.variations {
+            tokens.push("(".to_string());
+            write_moves(tokens, variation, &game);
+            tokens.push(")".to_string());
+            interrupted = true;
+        }
+
+        game.make_move_unchecked(m.mv.0, m.mv.1, m.mv.2);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Result(String),
+    San(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut line_start = true;
+
+    while let Some((start, ch)) = chars.next() {
+        let at_line_start = line_start;
+        line_start = ch == '\n';
+        match ch {
+            _ if ch.is_whitespace() => {}
+            // Escape mechanism, the whole line is ignored
+            '%' if at_line_start => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        line_start = true;
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                let mut comment = String::new();
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        line_start = true;
+                        break;
+                    }
+                    comment.push(c);
+                }
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some((_, c)) => comment.push(c),
+                        None => return Err(PgnError::UnterminatedComment),
+                    }
+                }
+                tokens.push(Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" ")));
+            }
+            '[' => {
+                let mut end = start + 1;
+                let mut in_string = false;
+                let mut escaped = false;
+                let mut closed = false;
+                for (i, c) in chars.by_ref() {
+                    end = i + c.len_utf8();
+                    match c {
+                        _ if escaped => escaped = false,
+                        '\\' if in_string => escaped = true,
+                        '"' => in_string = !in_string,
+                        ']' if !in_string => {
+                            closed = true;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                let tag = &text[start..end];
+                if !closed {
+                    return Err(PgnError::BadTag(tag.to_string()));
+                }
+                tokens.push(parse_tag(tag)?);
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            _ => {
+                let mut end = start + ch.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || "(){}[];".contains(c) || (c == '$' && i > start) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                symbol_tokens(&text[start..end], &mut tokens)?;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_tag(tag: &str) -> Result<Token, PgnError> {
+    let bad_tag = || PgnError::BadTag(tag.to_string());
+    let inner = tag[1..tag.len() - 1].trim();
+    let (name, value) = inner.split_once(char::is_whitespace).ok_or_else(bad_tag)?;
+    let value = value.trim();
+    if name.is_empty() || value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return Err(bad_tag());
+    }
+
+    let mut unescaped = String::new();
+    let mut escaped = false;
+    for c in value[1..value.len() - 1].chars() {
+        if c == '\\' && !escaped {
+            escaped = true;
+        } else {
+            unescaped.push(c);
+            escaped = false;
+        }
+    }
+    Ok(Token::Tag(name.to_string(), unescaped))
+}
+
+/// Splits a bare symbol, which may be a move number, NAG, result or SAN move with a
+/// `!`/`?` suffix, into tokens.
+fn symbol_tokens(symbol: &str, tokens: &mut Vec<Token>) -> Result<(), PgnError> {
+    if let Some(nag) = symbol.strip_prefix('$') {
+        let nag = nag
+            .parse()
+            .map_err(|_| PgnError::UnexpectedToken(symbol.to_string()))?;
+        tokens.push(Token::Nag(nag));
+        return Ok(());
+    }
+    if matches!(symbol, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        tokens.push(Token::Result(symbol.to_string()));
+        return Ok(());
+    }
+
+    // "12." and "12..." are move numbers, possibly written straight onto the move as in "12.Nf3"
+    let mut san = symbol;
+    let after_digits = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    if after_digits.len() < symbol.len() && after_digits.starts_with('.') {
+        san = after_digits.trim_start_matches('.');
+    }
+    if san.is_empty() {
+        return Ok(());
+    }
+
+    let move_part = san.trim_end_matches(['!', '?']);
+    if move_part.is_empty() {
+        return Err(PgnError::UnexpectedToken(symbol.to_string()));
+    }
+    tokens.push(Token::San(move_part.to_string()));
+    let nag = match &san[move_part.len()..] {
+        "" => None,
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => return Err(PgnError::UnexpectedToken(symbol.to_string())),
+    };
+    tokens.extend(nag.map(Token::Nag));
+    Ok(())
+}
+
+/// A line being read: its moves so far, the position after them and the one before the last.
+struct Line {
+    moves: Vec<PgnMove>,
+    position: Game,
+    before_last: Game,
+    pending_comment: Option<String>,
+}
+
+impl Line {
+    fn new(position: Game) -> Self {
+        Self {
+            moves: Vec::new(),
+            before_last: position.clone(),
+            position,
+            pending_comment: None,
+        }
+    }
+}
+
+fn join_comment(existing: &mut Option<String>, comment: String) {
+    match existing {
+        Some(text) => {
+            text.push(' ');
+            text.push_str(&comment);
+        }
+        None => *existing = Some(comment),
+    }
+}
+
+/// Reads every game in `text`. A game ends at its result, or where the tags of the next
+/// game begin if the result is missing.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = Vec::new();
+    let mut tokens = tokenize(text)?.into_iter().peekable();
+
+    while tokens.peek().is_some() {
+        let mut tags = Vec::new();
+        while let Some(Token::Tag(name, value)) = tokens.next_if(|t| matches!(t, Token::Tag(..))) {
+            tags.push((name, value));
+        }
+
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => Game::from_fen(fen)?,
+            None => Game::new(),
+        };
+        let mut lines = vec![Line::new(start.clone())];
+        let mut result = None;
+
+        while let Some(token) = tokens.next_if(|t| !matches!(t, Token::Tag(..))) {
+            let line = lines.last_mut().unwrap();
+            match token {
+                Token::Tag(..) => unreachable!(),
+                Token::Comment(comment) => match line.moves.last_mut() {
+                    Some(last) if line.pending_comment.is_none() => join_comment(&mut last.comment, comment),
+                    _ => join_comment(&mut line.pending_comment, comment),
+                },
+                Token::Nag(nag) => match line.moves.last_mut() {
+                    Some(last) => last.nags.push(nag),
+                    None => return Err(PgnError::UnexpectedToken(format!("${nag}"))),
+                },
+                Token::San(san) => {
+                    let mv = line.position.parse_san(&san).map_err(|error| PgnError::BadMove {
+                        move_number: line.position.fullmove_number,
+                        san: san.clone(),
+                        error,
+                    })?;
+                    line.before_last = line.position.clone();
+                    line.position.make_move_unchecked(mv.0, mv.1, mv.2);
+                    line.moves.push(PgnMove {
+                        comment_before: line.pending_comment.take(),
+                        ..PgnMove::new(mv)
+                    });
+                }
+                Token::Open => {
+                    if line.moves.is_empty() {
+                        return Err(PgnError::UnexpectedToken("(".to_string()));
+                    }
+                    let from = line.before_last.clone();
+                    lines.push(Line::new(from));
+                }
+                Token::Close => {
+                    if lines.len() == 1 {
+                        return Err(PgnError::UnexpectedToken(")".to_string()));
+                    }
+                    let mut variation = lines.pop().unwrap();
+                    let branch = lines.last_mut().unwrap().moves.last_mut().unwrap();
+                    // A comment still waiting for a move belongs to the end of the variation,
+                    // or to the move it branches from when the variation has no moves.
+                    if let Some(comment) = variation.pending_comment.take() {
+                        match variation.moves.last_mut() {
+                            Some(last) => join_comment(&mut last.comment, comment),
+                            None => join_comment(&mut branch.comment, comment),
+                        }
+                    }
+                    branch.variations.push(variation.moves);
+                }
+                Token::Result(r) => {
+                    if lines.len() > 1 {
+                        return Err(PgnError::UnterminatedVariation);
+                    }
+                    result = Some(r);
+                    break;
+                }
+            }
+        }
+
+        if lines.len() > 1 {
+            return Err(PgnError::UnterminatedVariation);
+        }
+        let main_line = lines.pop().unwrap();
+        let result = result
+            .or_else(|| tags.iter().find(|(name, _)| name == "Result").map(|(_, r)| r.clone()))
+            .unwrap_or_else(|| "*".to_string());
+
+        games.push(PgnGame {
+            tags,
+            start,
+            moves: main_line.moves,
+            result,
+        });
+    }
+
+    Ok(games)
+}
@@ -0,0 +1,225 @@
+use std::fmt;
+
+use crate::search::pst::get_piece_at;
+use crate::{Game, Piece};
+
+/// Why a SAN move could not be resolved against the current position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SanError {
+    /// Not SAN at all, e.g. a missing destination square or an unknown piece letter.
+    Malformed(String),
+    /// Well-formed, but no legal move in the position matches it.
+    IllegalMove(String),
+    /// More than one legal move matches, the candidates are given in UCI notation.
+    AmbiguousMove { san: String, candidates: Vec<String> },
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Malformed(san) => write!(f, "'{san}' is not a SAN move"),
+            SanError::IllegalMove(san) => write!(f, "'{san}' is not legal in this position"),
+            SanError::AmbiguousMove { san, candidates } => write!(
+                f,
+                "'{san}' is ambiguous, it matches {}",
+                candidates.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+fn piece_letter(piece: Piece) -> Option<char> {
+    match piece {
+        Piece::Pawn => None,
+        Piece::Knight => Some('N'),
+        Piece::Bishop => Some('B'),
+        Piece::Rook => Some('R'),
+        Piece::Queen => Some('Q'),
+        Piece::King => Some('K'),
+    }
+}
+
+fn piece_from_letter(letter: char) -> Option<Piece> {
+    match letter {
+        'N' => Some(Piece::Knight),
+        'B' => Some(Piece::Bishop),
+        'R' => Some(Piece::Rook),
+        'Q' => Some(Piece::Queen),
+        'K' => Some(Piece::King),
+        _ => None,
+    }
+}
+
+fn square_from_chars(file: char, rank: char) -> Option<usize> {
+    let file = (file as u8).checked_sub(b'a').filter(|&f| f < 8)?;
+    let rank = (rank as u8).checked_sub(b'1').filter(|&r| r < 8)?;
+    Some((rank * 8 + file) as usize)
+}
+
+fn square_name(square: usize) -> String {
+    let file = (b'a' + (square % 8) as u8) as char;
+    let rank = (b'1' + (square / 8) as u8) as char;
+    format!("{file}{rank}")
+}
+
+impl Game {
+    /// Writes a legal move in Standard Algebraic Notation, e.g. `Nbd7`, `exd6`, `e8=Q+` or `O-O#`.
+    pub fn move_to_san(&self, mv: (usize, usize, Option<Piece>)) -> String {
+        let (from, to, promo) = mv;
+        let piece = get_piece_at(self, from).expect("move_to_san: no piece on the 'from' square");
+
+        let mut san = if piece == Piece::King && (from as i8 - to as i8).abs() == 2 {
+            if to % 8 == 6 { "O-O" } else { "O-O-O" }.to_string()
+        } else {
+            let is_capture = get_piece_at(self, to).is_some()
+                || (piece == Piece::Pawn && Some(to) == self.en_passent);
+            let mut san = String::new();
+
+            match piece_letter(piece) {
+                Some(letter) => {
+                    san.push(letter);
+                    san.push_str(&self.disambiguation(piece, from, to));
+                }
+                // Pawn captures always name the file they come from
+                None if is_capture => san.push((b'a' + (from % 8) as u8) as char),
+                None => {}
+            }
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&square_name(to));
+            if let Some(promo) = promo.and_then(piece_letter) {
+                san.push('=');
+                san.push(promo);
+            }
+            san
+        };
+
+        let mut after = self.clone();
+        after.make_move_unchecked(from, to, promo);
+        if after.is_in_check() {
+            san.push(if after.generate_legal_moves().is_empty() { '#' } else { '+' });
+        }
+        san
+    }
+
+    /// The file, rank or square needed to tell a move apart from other moves of the same
+    /// piece type to the same square, empty if there are none.
+    fn disambiguation(&self, piece: Piece, from: usize, to: usize) -> String {
+        let others: Vec<usize> = self
+            .generate_legal_moves()
+            .iter()
+            .filter(|m| m.1 == to && m.0 != from && get_piece_at(self, m.0) == Some(piece))
+            .map(|m| m.0)
+            .collect();
+
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|sq| sq % 8 != from % 8) {
+            square_name(from)[..1].to_string()
+        } else if others.iter().all(|sq| sq / 8 != from / 8) {
+            square_name(from)[1..].to_string()
+        } else {
+            square_name(from)
+        }
+    }
+
+    /// Resolves a SAN move against the legal moves of this position. Check and annotation
+    /// suffixes such as `+`, `#`, `!` or `?!` are accepted and ignored, as is `0-0` for `O-O`.
+    pub fn parse_san(&self, san: &str) -> Result<(usize, usize, Option<Piece>), SanError> {
+        let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let malformed = || SanError::Malformed(san.to_string());
+        let legal_moves = self.generate_legal_moves();
+
+        let castle_file = match text {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+
+        let candidates: Vec<(usize, usize, Option<Piece>)> = if let Some(file) = castle_file {
+            legal_moves
+                .iter()
+                .filter(|m| {
+                    get_piece_at(self, m.0) == Some(Piece::King)
+                        && (m.0 as i8 - m.1 as i8).abs() == 2
+                        && m.1 % 8 == file
+                })
+                .copied()
+                .collect()
+        } else {
+            let mut chars: Vec<char> = text.chars().collect();
+
+            // Promotion, written "e8=Q" or "e8Q"
+            let mut promo = None;
+            if let Some(&last) = chars.last()
+                && let Some(piece) = piece_from_letter(last).filter(|p| *p != Piece::King)
+                && chars.len() > 2
+                && chars[chars.len() - 2] != 'x'
+                && !chars[chars.len() - 2].is_ascii_uppercase()
+            {
+                promo = Some(piece);
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+
+            let piece = match chars.first() {
+                Some(&letter) if letter.is_ascii_uppercase() => {
+                    chars.remove(0);
+                    piece_from_letter(letter).ok_or_else(malformed)?
+                }
+                _ => Piece::Pawn,
+            };
+
+            if chars.len() < 2 {
+                return Err(malformed());
+            }
+            let rank = chars.pop().unwrap();
+            let file = chars.pop().unwrap();
+            let to = square_from_chars(file, rank).ok_or_else(malformed)?;
+
+            // Whatever is left is an optional origin file and/or rank, and the capture mark
+            if chars.last() == Some(&'x') {
+                chars.pop();
+            }
+            let mut from_file = None;
+            let mut from_rank = None;
+            for ch in chars {
+                match ch {
+                    'a'..='h' if from_file.is_none() && from_rank.is_none() => {
+                        from_file = Some((ch as u8 - b'a') as usize)
+                    }
+                    '1'..='8' if from_rank.is_none() => from_rank = Some((ch as u8 - b'1') as usize),
+                    _ => return Err(malformed()),
+                }
+            }
+
+            legal_moves
+                .iter()
+                .filter(|m| {
+                    m.1 == to
+                        && m.2 == promo
+                        && get_piece_at(self, m.0) == Some(piece)
+                        // A pawn capture is always written with the file it comes from
+                        && (piece != Piece::Pawn || m.0 % 8 == m.1 % 8 || from_file.is_some())
+                        && from_file.is_none_or(|f| m.0 % 8 == f)
+                        && from_rank.is_none_or(|r| m.0 / 8 == r)
+                })
+                .copied()
+                .collect()
+        };
+
+        match candidates.as_slice() {
+            [mv] => Ok(*mv),
+            [] => Err(SanError::IllegalMove(san.to_string())),
+            _ => Err(SanError::AmbiguousMove {
+                san: san.to_string(),
+                candidates: candidates.iter().map(|m| crate::search::move_to_uci(*m)).collect(),
+            }),
+        }
+    }
+}
@@ -1,10 +1,6 @@
 // use std::time::Instant;
 
-use chess_bot::board::display::print_board;
-use chess_bot::search::eval::eval;
-use chess_bot::search::find_best_move;
 // use std::time::Instant;
-use chess_bot::Game;
 
 use chess_bot::uci::uci_loop;
 
@@ -37,14 +33,18 @@ fn main() {
     //     }
     // }
 
-    let file_path = "./src/utils/perftsuite.txt";
-    println!("--- Running Perft Test Suite from '{file_path}' ---");
+    // The perft suite is still available with `chess_bot perft`, otherwise speak UCI.
+    if std::env::args().nth(1).as_deref() == Some("perft") {
+        let file_path = "./src/utils/perftsuite.txt";
+        println!("--- Running Perft Test Suite from '{file_path}' ---");
 
-    if let Err(e) = chess_bot::utils::test_runner::run_tests(file_path) {
-        eprintln!("Error running test suite: {e}");
+        if let Err(e) = chess_bot::utils::test_runner::run_tests(file_path) {
+            eprintln!("Error running test suite: {e}");
+        }
+        return;
     }
 
     // let duration = start.elapsed();
     // println!("Time taken: {duration:.3?}");
-    // uci_loop();
+    uci_loop();
 }
@@ -1,65 +1,138 @@
+use super::history::PieceTo;
 use super::see;
-use super::tt::Flag;
+use super::tt::{Flag, score_from_tt, score_to_tt};
+use super::{DELTA_MARGIN, MATE_SCORE, MAX_PLY, get_piece_value, left_king_in_check};
+use crate::board::movegen::{
+    generate_pseudo_legal_captures, generate_pseudo_legal_moves, generate_pseudo_legal_quiets,
+};
 use crate::game::Game;
 use crate::search::pst::{
     ADJACENT_FILES_MASKS, EG_TABLE, FILE_MASKS, MG_TABLE, PASSED_BLACK_MASKS, PASSED_WHITE_MASKS,
     get_piece_at,
 };
-use crate::search::{MAX_PHASE, PHASE_WEIGHTS, PIECE_VALUES, Search, TEMPO_BONUS, pst};
+use crate::search::{MAX_PHASE, PHASE_WEIGHTS, PIECE_VALUES, Search, TEMPO_BONUS};
 use crate::{Bitboard, Piece}; 
 
+/// Searches captures until the position is quiet, so the static eval is never taken in the
+/// middle of an exchange. In check every move is searched instead, as standing pat is not an
+/// option there. With `checks`, quiet moves and otherwise pruned captures that give check are
+/// tried as well, which the main search asks for at the first ply only.
 pub fn quiescence_search(
     game: &mut Game,
+    ply: usize,
     mut alpha: i32,
     beta: i32,
+    checks: bool,
     search_helper: &mut Search,
 ) -> i32 {
     search_helper.nodes_searched += 1;
-    let tt_entry = game.tt.lock().unwrap().probe(game.zobrist_hash);
-    let stand_pat = if let Some(entry) = tt_entry {
-        if entry.flag == Flag::Exact {
-            entry.score
-        } else {
-            eval(game) // Fallback to fresh evaluation
-        }
-    } else {
-        eval(game) // No TT entry, so evaluate from scratch
-    };
+    search_helper.sel_depth = search_helper.sel_depth.max(ply);
+    if ply >= MAX_PLY - 1 {
+        return eval(game);
+    }
 
-    if stand_pat >= beta {
-        return beta;
+    let key = game.zobrist_hash;
+    let tt_entry = game.tt.probe(key);
+    if let Some(entry) = tt_entry {
+        let score = score_from_tt(entry.score, ply);
+        match entry.flag {
+            Flag::Exact => return score,
+            Flag::LowerBound if score >= beta => return beta,
+            Flag::UpperBound if score <= alpha => return alpha,
+            _ => {}
+        }
     }
-    if alpha < stand_pat {
-        alpha = stand_pat;
+    let tt_move = tt_entry.and_then(|entry| entry.best_move);
+    // Results are stored at depth 0, and never in place of an entry from the main search.
+    let can_store = tt_entry.is_none_or(|entry| entry.depth == 0);
+
+    let in_check = game.is_in_check();
+    let static_eval = (!in_check).then(|| {
+        tt_entry
+            .and_then(|entry| entry.static_eval)
+            .unwrap_or_else(|| eval(game))
+    });
+    let original_alpha = alpha;
+
+    if let Some(stand_pat) = static_eval {
+        if stand_pat >= beta {
+            if can_store {
+                game.tt
+                    .store(key, 0, score_to_tt(beta, ply), Flag::LowerBound, None, static_eval);
+            }
+            return beta;
+        }
+        alpha = alpha.max(stand_pat);
     }
 
-    let enemy_pieces = if game.is_white_turn {
-        game.board.black_pieces()
+    let mut moves: Vec<_> = if in_check {
+        generate_pseudo_legal_moves(game).iter().copied().collect()
     } else {
-        game.board.white_pieces()
+        generate_pseudo_legal_captures(game).iter().copied().collect()
     };
-    let mut moves = game.generate_legal_moves();
-    moves.retain(|&(_, to, promo)| ((1u64 << to) & enemy_pieces != 0) || promo.is_some());
-
-    let ply = pst::ply(game) as usize;
-    moves.sort_by_cached_key(|m| -(see::score_move(game, *m, ply, search_helper)));
+    moves.sort_by_cached_key(|m| -(see::score_move(game, *m, ply, tt_move, search_helper)));
+    let capture_count = moves.len();
+    if checks && !in_check {
+        moves.extend(generate_pseudo_legal_quiets(game).iter().copied());
+    }
 
-    for m in moves.iter() {
-        if see::static_exchange_exchange(game, m.0, m.1) < 0 {
+    let mut best_move = None;
+    let mut legal_moves = 0;
+    for (i, &m) in moves.iter().enumerate() {
+        let is_quiet = i >= capture_count;
+        // Captures that lose material, or cannot bring us up to alpha even winning the piece
+        // for free (delta pruning), are skipped unless they give check at the first ply.
+        let futile = match static_eval {
+            Some(stand_pat) if !is_quiet => {
+                let captured = get_piece_at(game, m.1).unwrap_or(Piece::Pawn);
+                see::static_exchange_exchange(game, m.0, m.1) < 0
+                    || (m.2.is_none() && stand_pat + get_piece_value(captured) + DELTA_MARGIN <= alpha)
+            }
+            _ => false,
+        };
+        if futile && !checks {
             continue;
         }
 
+        let piece = get_piece_at(game, m.0);
+        search_helper.moved[ply] = piece.map(|p| PieceTo::new(p, game.is_white_turn, m.1));
         let undo = game.make_move_unchecked(m.0, m.1, m.2);
-        let score = -quiescence_search(game, -beta, -alpha, search_helper);
+        // Quiet and futile moves are only of interest here when they give check.
+        if left_king_in_check(game) || ((is_quiet || futile) && !game.is_in_check()) {
+            game.unmake_move(undo);
+            continue;
+        }
+        legal_moves += 1;
+
+        let score = -quiescence_search(game, ply + 1, -beta, -alpha, false, search_helper);
         game.unmake_move(undo);
 
         if score >= beta {
+            if can_store {
+                game.tt
+                    .store(key, 0, score_to_tt(beta, ply), Flag::LowerBound, Some(m), static_eval);
+            }
             return beta;
         }
         if score > alpha {
             alpha = score;
+            best_move = Some(m);
         }
     }
+
+    if in_check && legal_moves == 0 {
+        return -MATE_SCORE + ply as i32;
+    }
+
+    if can_store {
+        let flag = if alpha > original_alpha {
+            Flag::Exact
+        } else {
+            Flag::UpperBound
+        };
+        game.tt
+            .store(key, 0, score_to_tt(alpha, ply), flag, best_move, static_eval);
+    }
     alpha
 }
 
@@ -0,0 +1,127 @@
+use std::fmt;
+
+use crate::Piece;
+
+type Move = (usize, usize, Option<Piece>);
+// Indexed by the previous move's piece and square, then the current move's.
+type ContinuationTable = [[[[i16; 64]; 12]; 64]; 12];
+
+// Every history score stays within plus or minus this value, see `apply_gravity`.
+pub const HISTORY_MAX: i32 = 16_384;
+const HISTORY_BONUS_MAX: i32 = 1_200;
+
+/// A piece of one colour and the square it moved to, the key of most history tables.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PieceTo {
+    pub piece: usize,
+    pub to: usize,
+}
+
+impl PieceTo {
+    pub fn new(piece: Piece, is_white: bool, to: usize) -> Self {
+        Self {
+            piece: piece as usize + if is_white { 0 } else { 6 },
+            to,
+        }
+    }
+}
+
+/// Move ordering statistics learned from beta cutoffs. They are kept from one search to
+/// the next and only aged in between, so the engine does not relearn them on every move.
+#[derive(Clone)]
+pub struct History {
+    quiet: [[i16; 64]; 12],
+    // Indexed by the moving piece and square, then the type of the captured piece.
+    capture: [[[i16; 6]; 64]; 12],
+    // Quiet history conditioned on the move one ply (index 0) and two plies (index 1) earlier.
+    continuation: [Box<ContinuationTable>; 2],
+    // The quiet move that last refuted each previous move.
+    countermoves: [[Option<Move>; 64]; 12],
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            quiet: [[0; 64]; 12],
+            capture: [[[0; 6]; 64]; 12],
+            continuation: [
+                vec![[[[0; 64]; 12]; 64]; 12].try_into().unwrap(),
+                vec![[[[0; 64]; 12]; 64]; 12].try_into().unwrap(),
+            ],
+            countermoves: [[None; 64]; 12],
+        }
+    }
+}
+
+impl fmt::Debug for History {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("History").finish_non_exhaustive()
+    }
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Halves every score, called before each new search so old statistics still guide the
+    /// first iterations but are soon outweighed by what the new search learns.
+    pub fn age(&mut self) {
+        let halve = |score: &mut i16| *score /= 2;
+        self.quiet.iter_mut().flatten().for_each(halve);
+        self.capture.iter_mut().flatten().flatten().for_each(halve);
+        for table in &mut self.continuation {
+            table.iter_mut().flatten().flatten().flatten().for_each(halve);
+        }
+    }
+
+    /// Ordering score of a quiet move, given the moves made one and two plies earlier.
+    pub fn quiet_score(&self, moved: PieceTo, previous: [Option<PieceTo>; 2]) -> i32 {
+        let mut score = self.quiet[moved.piece][moved.to] as i32;
+        for (table, prev) in self.continuation.iter().zip(previous) {
+            if let Some(prev) = prev {
+                score += table[prev.piece][prev.to][moved.piece][moved.to] as i32;
+            }
+        }
+        score
+    }
+
+    pub fn capture_score(&self, moved: PieceTo, captured: Piece) -> i32 {
+        self.capture[moved.piece][moved.to][captured as usize] as i32
+    }
+
+    pub fn countermove(&self, previous: PieceTo) -> Option<Move> {
+        self.countermoves[previous.piece][previous.to]
+    }
+
+    /// Rewards a quiet move that caused a cutoff, or punishes one that did not when `bonus` is negative.
+    pub fn update_quiet(&mut self, moved: PieceTo, previous: [Option<PieceTo>; 2], bonus: i32) {
+        apply_gravity(&mut self.quiet[moved.piece][moved.to], bonus);
+        for (table, prev) in self.continuation.iter_mut().zip(previous) {
+            if let Some(prev) = prev {
+                apply_gravity(&mut table[prev.piece][prev.to][moved.piece][moved.to], bonus);
+            }
+        }
+    }
+
+    pub fn update_capture(&mut self, moved: PieceTo, captured: Piece, bonus: i32) {
+        apply_gravity(&mut self.capture[moved.piece][moved.to][captured as usize], bonus);
+    }
+
+    pub fn set_countermove(&mut self, previous: PieceTo, mv: Move) {
+        self.countermoves[previous.piece][previous.to] = Some(mv);
+    }
+}
+
+/// History bonus for a cutoff at `depth`, squared so deep cutoffs count the most.
+pub fn history_bonus(depth: u8) -> i32 {
+    (16 * depth as i32 * depth as i32).min(HISTORY_BONUS_MAX)
+}
+
+/// Moves `score` towards the bound in the direction of `bonus`, by less the closer it already
+/// is, so scores saturate instead of growing without limit and recent results weigh most.
+fn apply_gravity(score: &mut i16, bonus: i32) {
+    let bonus = bonus.clamp(-HISTORY_MAX, HISTORY_MAX);
+    let current = *score as i32;
+    *score = (current + bonus - current * bonus.abs() / HISTORY_MAX) as i16;
+}
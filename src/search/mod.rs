@@ -1,28 +1,73 @@
 pub mod eval;
+pub mod history;
+pub mod movepick;
 pub mod pst;
 pub mod see;
+pub mod time;
 pub mod tt;
 pub mod zobrist;
 
-use self::tt::Flag;
-use crate::Piece;
+use self::history::{History, PieceTo, history_bonus};
+use self::movepick::MovePicker;
+use self::time::TimeManager;
+use self::tt::{Flag, score_from_tt, score_to_tt};
+use crate::{MoveList, Piece};
 use crate::game::Game;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
 
 const MAX_LMR_DEPTH: usize = 64;
 const MAX_LMR_MOVES: usize = 64;
 
-const MATE_SCORE: i32 = i32::MAX;
-const MATE_THRESHOLD: i32 = MATE_SCORE / 2;
+// Must fit the 16 bit score field of the transposition table.
+pub const MATE_SCORE: i32 = 30_000;
+pub const MATE_THRESHOLD: i32 = MATE_SCORE / 2;
 const TEMPO_BONUS: i32 = 10;
 
 pub const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0]; // pawn to king
 pub const MAX_PHASE: i32 = 24;
 
+// The TT move is ordered ahead of everything else, it was best the last time we were here.
+const TT_MOVE_SCORE: i32 = 1_000_000;
 const KILLER_MOVE_SCORE: i32 = 700_000;
 
+// Aspiration windows start this far (in centipawns) either side of the previous
+// iteration's score, from this depth on, and double on every fail.
+const ASPIRATION_WINDOW: i32 = 25;
+const ASPIRATION_MIN_DEPTH: u8 = 4;
+
+// Extensions stop once a line is this many times as long as the root depth.
+const EXTENSION_PLY_FACTOR: usize = 2;
+// Singular extensions: minimum depth, and how much the stored TT entry may be shallower.
+const SINGULAR_MIN_DEPTH: u8 = 6;
+const SINGULAR_TT_DEPTH_MARGIN: u8 = 3;
+
+// Null-move pruning: minimum depth, base reduction, and the depth from which a
+// null-move cutoff is verified by a normal reduced search to guard against zugzwang.
+const NMP_MIN_DEPTH: u8 = 3;
+const NMP_BASE_REDUCTION: u8 = 3;
+const NMP_VERIFICATION_DEPTH: u8 = 10;
+
+// Forward pruning, all of it only at shallow depths. Reverse futility pruning returns when
+// the static eval beats beta by a margin per ply, razoring drops to quiescence search when it
+// is far below alpha, futility pruning skips quiet moves that cannot raise it to alpha, and
+// late-move pruning skips quiet moves after the first few of the list.
+const RFP_MAX_DEPTH: u8 = 6;
+const RFP_MARGIN: i32 = 80;
+const RAZORING_MAX_DEPTH: u8 = 3;
+const RAZORING_MARGIN: i32 = 250;
+const FUTILITY_MAX_DEPTH: u8 = 6;
+const FUTILITY_MARGIN: i32 = 120;
+const LMP_MAX_DEPTH: u8 = 4;
+const LMP_BASE_MOVES: usize = 3;
+// Quiescence search skips captures that leave it this far below alpha even after winning the piece.
+const DELTA_MARGIN: i32 = 200;
+
+// Root moves are only announced with `info currmove` once the search has run this long.
+const CURRMOVE_REPORT_DELAY: Duration = Duration::from_millis(3000);
+
 pub const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 20000];
 const MAX_PLY: usize = 64;
 type KillerMove = Option<(usize, usize, Option<Piece>)>;
@@ -30,18 +75,164 @@ type KillerMove = Option<(usize, usize, Option<Piece>)>;
 static LMR_TABLE: [[u8; MAX_LMR_MOVES]; MAX_LMR_DEPTH] =
     unsafe { std::mem::transmute(*include_bytes!(concat!(env!("OUT_DIR"), "/lmr.bin"))) };
 
+/// Everything that can end a search, as sent with `go`.
+#[derive(Clone, Debug)]
+pub struct SearchLimits {
+    pub depth: u8,
+    pub nodes: Option<u64>,
+    /// Stop as soon as a mate in at most this many moves is found.
+    pub mate: Option<u8>,
+    /// Keep searching until the stop signal is raised, even after `depth` is reached.
+    pub infinite: bool,
+    /// Restricts the root moves, an empty list means every legal move.
+    pub search_moves: Vec<(usize, usize, Option<Piece>)>,
+    pub time: TimeManager,
+    /// Set while searching on the opponent's time, cleared by `ponderhit`.
+    pub ponder: Option<Arc<AtomicBool>>,
+    /// Number of search threads, helpers only share the transposition table with the main thread.
+    pub threads: usize,
+    /// Append a win/draw/loss estimate to every `info score`, as requested by `UCI_ShowWDL`.
+    pub show_wdl: bool,
+    /// Number of best root moves to search and report, each with its own `info multipv` line.
+    pub multi_pv: usize,
+    /// Centipawns a draw is worth less than an equal position to the side we search for.
+    pub contempt: i32,
+    pub pruning: Pruning,
+    /// Principal variation search and aspiration windows. Without them every move is
+    /// searched with the full window, only useful to measure what they save.
+    pub narrow_windows: bool,
+    /// Move ordering history carried from one search to the next. It is aged when the search
+    /// starts and replaced by the main thread's tables when it ends.
+    pub history: Option<Arc<Mutex<History>>>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            depth: u8::MAX,
+            nodes: None,
+            mate: None,
+            infinite: false,
+            search_moves: Vec::new(),
+            time: TimeManager::infinite(),
+            ponder: None,
+            threads: 1,
+            show_wdl: false,
+            multi_pv: 1,
+            contempt: 0,
+            pruning: Pruning::default(),
+            narrow_windows: true,
+            history: None,
+        }
+    }
+}
+
+/// Forward pruning techniques the search may use, each can be switched off for A/B testing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pruning {
+    pub reverse_futility: bool,
+    pub razoring: bool,
+    pub futility: bool,
+    pub late_move: bool,
+}
+
+impl Default for Pruning {
+    fn default() -> Self {
+        Self {
+            reverse_futility: true,
+            razoring: true,
+            futility: true,
+            late_move: true,
+        }
+    }
+}
+
+impl SearchLimits {
+    /// Limits for a plain fixed-depth search.
+    pub fn depth(depth: u8) -> Self {
+        Self {
+            depth,
+            ..Self::default()
+        }
+    }
+
+    /// Returns true until the GUI confirms the ponder move with `ponderhit`.
+    pub fn is_pondering(&self) -> bool {
+        self.ponder
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+}
+
+/// The outcome of a search as reported to the caller, or one line of a MultiPV analysis.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<(usize, usize, Option<Piece>)>,
+    pub score: i32,
+    pub pv: Vec<(usize, usize, Option<Piece>)>,
+    /// Nodes searched by all threads when this result was found.
+    pub nodes: u64,
+}
+
 pub struct Search {
     pub nodes_searched: u64,
+    /// Deepest ply reached in the current iteration, including quiescence search.
+    pub sel_depth: usize,
+    node_limit: Option<u64>,
+    // Only the main thread reports root moves, timed from the start of the search.
+    report_root_moves: bool,
+    time: TimeManager,
+    root_moves: Vec<(usize, usize, Option<Piece>)>,
+    // Root moves already reported as a better line during this MultiPV iteration.
+    excluded_root_moves: Vec<(usize, usize, Option<Piece>)>,
     killer_moves: [[KillerMove; 2]; MAX_PLY],
-    history: [[i32; 64]; 12],
+    history: History,
+    // The piece and destination of the move leading to each ply, None after a null move.
+    moved: [Option<PieceTo>; MAX_PLY],
+    // Triangular PV table: row `ply` holds the best line found from that ply onwards.
+    pv_table: [[(usize, usize, Option<Piece>); MAX_PLY]; MAX_PLY],
+    pv_length: [usize; MAX_PLY],
+    // Whether the move leading to each ply was a null move, so two are never played in a row.
+    null_moved: [bool; MAX_PLY],
+    // Set during a zugzwang verification search, where null moves are not allowed.
+    verifying_null_move: bool,
+    // Depth of the current iteration, used to cap extensions.
+    root_depth: u8,
+    // Square the move leading to each ply captured on, for recapture extensions.
+    capture_square: [Option<usize>; MAX_PLY],
+    // Move skipped at each ply while testing whether the TT move is singular.
+    excluded_move: [KillerMove; MAX_PLY],
+    contempt: i32,
+    // Side to move at the root, contempt is always from its point of view.
+    root_is_white: bool,
+    pruning: Pruning,
+    narrow_windows: bool,
 }
 
 impl Default for Search {
     fn default() -> Self {
         Self {
             nodes_searched: 0,
+            sel_depth: 0,
+            node_limit: None,
+            report_root_moves: false,
+            time: TimeManager::infinite(),
+            root_moves: Vec::new(),
+            excluded_root_moves: Vec::new(),
             killer_moves: [[None; 2]; MAX_PLY],
-            history: [[0; 64]; 12],
+            history: History::default(),
+            moved: [None; MAX_PLY],
+            pv_table: [[(0, 0, None); MAX_PLY]; MAX_PLY],
+            pv_length: [0; MAX_PLY],
+            null_moved: [false; MAX_PLY],
+            verifying_null_move: false,
+            root_depth: 0,
+            capture_square: [None; MAX_PLY],
+            excluded_move: [None; MAX_PLY],
+            contempt: 0,
+            root_is_white: true,
+            pruning: Pruning::default(),
+            narrow_windows: true,
         }
     }
 }
@@ -51,6 +242,24 @@ impl Search {
         Self::default()
     }
 
+    /// Creates a search helper that honours the node limit and root move restriction.
+    pub fn with_limits(limits: &SearchLimits) -> Self {
+        Self {
+            node_limit: limits.nodes,
+            time: limits.time,
+            contempt: limits.contempt,
+            pruning: limits.pruning,
+            narrow_windows: limits.narrow_windows,
+            root_moves: limits.search_moves.clone(),
+            history: limits
+                .history
+                .as_ref()
+                .map(|history| history.lock().unwrap().clone())
+                .unwrap_or_default(),
+            ..Self::default()
+        }
+    }
+
     /// Adds a killer move for the current ply, shifting the existing one.
     fn add_killer_move(&mut self, ply: usize, mv: (usize, usize, Option<Piece>)) {
         if ply < MAX_PLY {
@@ -59,118 +268,456 @@ impl Search {
         }
     }
 
-    /// Updates the history score for a successful quiet move.
-    fn update_history_score(&mut self, piece: Piece, to: usize, depth: i32) {
-        // The bonus is squared to heavily reward cutoffs at higher depths.
-        self.history[piece as usize][to] += depth * depth;
+    /// Starts an empty PV for the node at `ply`.
+    fn clear_pv(&mut self, ply: usize) {
+        if ply < MAX_PLY {
+            self.pv_length[ply] = ply;
+        }
+    }
+
+    /// Makes `mv` followed by the child's PV the new PV at `ply`.
+    fn update_pv(&mut self, ply: usize, mv: (usize, usize, Option<Piece>)) {
+        if ply >= MAX_PLY {
+            return;
+        }
+        self.pv_table[ply][ply] = mv;
+
+        let child_length = if ply + 1 < MAX_PLY {
+            self.pv_length[ply + 1]
+        } else {
+            ply + 1
+        };
+        for i in ply + 1..child_length {
+            self.pv_table[ply][i] = self.pv_table[ply + 1][i];
+        }
+        self.pv_length[ply] = child_length.max(ply + 1);
+    }
+
+    /// The PV found by the last completed search from the root.
+    pub fn principal_variation(&self) -> Vec<(usize, usize, Option<Piece>)> {
+        self.pv_table[0][..self.pv_length[0]].to_vec()
+    }
+
+    /// Score of a drawn position for the side to move in `game`.
+    fn draw_score(&self, game: &Game) -> i32 {
+        if game.is_white_turn == self.root_is_white {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    /// The moves one and two plies before `ply`, which continuation history is keyed on.
+    fn previous_moves(&self, ply: usize) -> [Option<PieceTo>; 2] {
+        [
+            ply.checked_sub(1).and_then(|p| self.moved[p]),
+            ply.checked_sub(2).and_then(|p| self.moved[p]),
+        ]
+    }
+
+    /// Rewards the move that caused a beta cutoff and punishes the moves of the same kind
+    /// searched before it, which failed to.
+    fn update_cutoff_histories(
+        &mut self,
+        game: &Game,
+        ply: usize,
+        depth: u8,
+        cutoff: (usize, usize, Option<Piece>),
+        quiets_tried: &MoveList,
+        captures_tried: &MoveList,
+    ) {
+        let bonus = history_bonus(depth);
+        let piece_to = |m: &(usize, usize, Option<Piece>)| {
+            pst::get_piece_at(game, m.0).map(|piece| PieceTo::new(piece, game.is_white_turn, m.1))
+        };
+        let captured = |m: &(usize, usize, Option<Piece>)| {
+            pst::get_piece_at(game, m.1).unwrap_or(Piece::Pawn)
+        };
+
+        if is_capture(game, cutoff) {
+            if let Some(moved) = piece_to(&cutoff) {
+                self.history.update_capture(moved, captured(&cutoff), bonus);
+            }
+        } else if cutoff.2.is_none() {
+            self.add_killer_move(ply, cutoff);
+            let previous = self.previous_moves(ply);
+            if let Some(prev) = previous[0] {
+                self.history.set_countermove(prev, cutoff);
+            }
+            if let Some(moved) = piece_to(&cutoff) {
+                self.history.update_quiet(moved, previous, bonus);
+            }
+            for m in quiets_tried.iter() {
+                if let Some(moved) = piece_to(m) {
+                    self.history.update_quiet(moved, previous, -bonus);
+                }
+            }
+        }
+
+        // A capture that did not cut is worse than hoped for whatever refuted the position.
+        for m in captures_tried.iter() {
+            if let Some(moved) = piece_to(m) {
+                self.history.update_capture(moved, captured(m), -bonus);
+            }
+        }
     }
 }
 
+/// Material value of a piece. `PIECE_VALUES` runs from pawn to king, the reverse of `Piece`.
 fn get_piece_value(piece: Piece) -> i32 {
-    PIECE_VALUES[piece as usize]
+    PIECE_VALUES[5 - piece as usize]
+}
+
+/// True when `mv` takes an enemy piece, en passant included. Move ordering, pruning and the
+/// history tables all go by this, the same captures the move generator hands out as such.
+fn is_capture(game: &Game, mv: (usize, usize, Option<Piece>)) -> bool {
+    let enemy_pieces = if game.is_white_turn {
+        game.board.black_pieces()
+    } else {
+        game.board.white_pieces()
+    };
+    let is_en_passant =
+        game.en_passent == Some(mv.1) && pst::get_piece_at(game, mv.0) == Some(Piece::Pawn);
+    (1u64 << mv.1) & enemy_pieces != 0 || is_en_passant
 }
 
+/// Runs a Lazy SMP search: the calling thread reports progress and picks the move,
+/// while `limits.threads - 1` helpers search the same position to fill the shared table.
 pub fn find_best_move(
     game: &mut Game,
-    max_depth: u8,
+    limits: &SearchLimits,
     stop_signal: &Arc<AtomicBool>,
-) -> (Option<(usize, usize, Option<Piece>)>, i32) {
-    game.tt.lock().unwrap().clear();
-    let mut best_move = None;
-    let mut best_score = -MATE_SCORE;
-    let mut search_helper = Search::new();
-    let start_time = Instant::now();
+) -> SearchResult {
+    let mut lines = analyse(game, limits, stop_signal);
+    if !lines.is_empty() {
+        return lines.swap_remove(0);
+    }
 
-    for depth in 1..=max_depth {
-        let score = search(
+    // Not even the first iteration finished, fall back to any move we may play.
+    let mut legal_moves = game.generate_legal_moves();
+    if !limits.search_moves.is_empty() {
+        legal_moves.retain(|m| limits.search_moves.contains(m));
+    }
+    if legal_moves.is_empty() {
+        println!("info string no legal moves in this position");
+    }
+    SearchResult {
+        best_move: legal_moves.iter().next().copied(),
+        score: -MATE_SCORE,
+        pv: Vec::new(),
+        nodes: 0,
+    }
+}
+
+/// Searches the `limits.multi_pv` best root moves, each one excluding the moves found
+/// before it, and returns the lines of the last completed iteration ranked best first.
+pub fn analyse(
+    game: &mut Game,
+    limits: &SearchLimits,
+    stop_signal: &Arc<AtomicBool>,
+) -> Vec<SearchResult> {
+    game.tt.new_search();
+    if let Some(history) = &limits.history {
+        history.lock().unwrap().age();
+    }
+
+    // Helpers run until the main thread is done, whatever ended its search.
+    let helper_stop = Arc::new(AtomicBool::new(false));
+    let helper_nodes: Vec<AtomicU64> = (1..limits.threads.max(1))
+        .map(|_| AtomicU64::new(0))
+        .collect();
+
+    thread::scope(|scope| {
+        for (id, nodes) in helper_nodes.iter().enumerate() {
+            let mut helper_game = game.clone();
+            let helper_stop = &helper_stop;
+            scope.spawn(move || {
+                helper_search(&mut helper_game, limits, helper_stop, nodes, id + 1);
+            });
+        }
+
+        let lines = main_search(game, limits, stop_signal, &helper_nodes);
+        helper_stop.store(true, Ordering::Relaxed);
+        lines
+    })
+}
+
+/// Iterative deepening for a helper thread, which never reports anything itself.
+fn helper_search(
+    game: &mut Game,
+    limits: &SearchLimits,
+    stop_signal: &Arc<AtomicBool>,
+    nodes: &AtomicU64,
+    id: usize,
+) {
+    let mut search_helper = Search::with_limits(limits);
+    // Only the main thread counts towards "go nodes", so node limited searches stay deterministic.
+    search_helper.node_limit = None;
+
+    // Odd helpers start one ply deeper so the threads do not all search in lockstep.
+    let start_depth = 1 + (id % 2) as u8;
+    for depth in start_depth..=limits.depth {
+        search(
             game,
             depth,
+            0,
             -MATE_SCORE,
             MATE_SCORE,
             stop_signal,
             &mut search_helper,
         );
-
-        let duration = start_time.elapsed();
-        best_score = score;
+        nodes.store(search_helper.nodes_searched, Ordering::Relaxed);
 
         if stop_signal.load(Ordering::Relaxed) {
-            println!("STOPPED");
             break;
         }
+    }
+}
 
-        let mut pv = Vec::new();
-        let mut temp_game = game.clone(); // Create a temporary board to walk the PV
-        for _ in 0..depth {
-            let entry = game.tt.lock().unwrap().probe(temp_game.zobrist_hash);
-            if let Some(entry) = entry {
-                if let Some(mv) = entry.best_move {
-                    pv.push(mv);
-                    temp_game.make_move_unchecked(mv.0, mv.1, mv.2);
-                } else {
-                    break; // Stop if no best move is found
-                }
-            } else {
-                break; // Stop if position not in TT
+fn main_search(
+    game: &mut Game,
+    limits: &SearchLimits,
+    stop_signal: &Arc<AtomicBool>,
+    helper_nodes: &[AtomicU64],
+) -> Vec<SearchResult> {
+    let mut lines: Vec<SearchResult> = Vec::new();
+    let mut search_helper = Search::with_limits(limits);
+    search_helper.report_root_moves = true;
+    let time_manager = &limits.time;
+    // The soft limit of a ponder search only runs once the opponent played the expected move.
+    let mut move_clock = *time_manager;
+    let mut pondered = limits.is_pondering();
+    let max_depth = limits.depth;
+
+    let mut root_moves = game.generate_legal_moves();
+    if !limits.search_moves.is_empty() {
+        root_moves.retain(|m| limits.search_moves.contains(m));
+    }
+    let multi_pv = limits.multi_pv.clamp(1, root_moves.len().max(1));
+
+    'deepening: for depth in 1..=max_depth {
+        search_helper.sel_depth = 0;
+        search_helper.excluded_root_moves.clear();
+        let mut iteration = Vec::with_capacity(multi_pv);
+
+        for index in 0..multi_pv {
+            let previous_score = lines.get(index).map(|line| line.score);
+            let score = aspiration_search(
+                game,
+                depth,
+                previous_score,
+                stop_signal,
+                &mut search_helper,
+            );
+
+            // A partially searched iteration is not trusted, keep the previous result.
+            if stop_signal.load(Ordering::Relaxed) {
+                break 'deepening;
+            }
+
+            let mut pv = search_helper.principal_variation();
+            // The table only holds the best line overall, so it is no help for later lines.
+            if pv.is_empty() && search_helper.excluded_root_moves.is_empty() {
+                pv = pv_from_tt(game, depth as usize);
             }
+            let Some(&best_move) = pv.first() else {
+                break;
+            };
+
+            search_helper.excluded_root_moves.push(best_move);
+            iteration.push(SearchResult {
+                best_move: Some(best_move),
+                score,
+                pv,
+                nodes: 0,
+            });
         }
 
-        if !pv.is_empty() {
-            best_move = pv.first().copied();
-        } else if let Some(entry) = game.tt.lock().unwrap().probe(game.zobrist_hash) {
-            best_move = entry.best_move;
+        if iteration.is_empty() {
+            break;
         }
 
-        let pv_string = pv
-            .iter()
-            .map(|m| move_to_uci(*m))
-            .collect::<Vec<_>>()
-            .join(" ");
+        let duration = time_manager.elapsed();
+        let nodes = search_helper.nodes_searched
+            + helper_nodes
+                .iter()
+                .map(|n| n.load(Ordering::Relaxed))
+                .sum::<u64>();
 
-        if best_score.abs() >= MATE_THRESHOLD {
-            let mate_in = (MATE_SCORE - best_score.abs() + 1) / 2;
-            let sign = if best_score > 0 { "" } else { "-" };
-            println!(
-                "depth {depth} score mate {sign}{mate_in} time {} nodes {} pv {pv_string}",
-                duration.as_millis(),
-                search_helper.nodes_searched
-            );
-        } else {
+        // Later lines can fail to stay below earlier ones, so rank them by score.
+        iteration.sort_by_key(|line| -line.score);
+        for line in &mut iteration {
+            line.nodes = nodes;
+        }
+        lines = iteration;
+        let millis = duration.as_millis() as u64;
+        let nps = nodes * 1000 / millis.max(1);
+
+        for (index, line) in lines.iter().enumerate() {
+            let pv_string = line
+                .pv
+                .iter()
+                .map(|m| move_to_uci(*m))
+                .collect::<Vec<_>>()
+                .join(" ");
             println!(
-                "depth {depth} score cp {best_score} time {} nodes {} pv {pv_string}",
-                duration.as_millis(),
-                search_helper.nodes_searched
+                "info depth {depth} seldepth {} multipv {} score {} nodes {nodes} nps {nps} hashfull {} time {millis} pv {pv_string}",
+                search_helper.sel_depth.max(depth as usize),
+                index + 1,
+                format_score(line.score, limits.show_wdl),
+                game.tt.hashfull(),
             );
         }
 
-        if best_score >= MATE_SCORE - (max_depth as i32) {
-            println!("STOPPED2");
+        let score = lines[0].score;
+        if let Some(mate) = limits.mate
+            && mate_in(score).is_some_and(|n| n > 0 && n <= mate as i32)
+        {
+            break;
+        }
+
+        if limits.infinite || limits.is_pondering() {
+            continue;
+        }
+        // The first iteration to end after `ponderhit` starts the soft limit. The hard limit
+        // armed by `ponderhit` still caps an iteration that was running long at that point.
+        if pondered {
+            pondered = false;
+            move_clock.restart();
+        }
+
+        // Reductions can hide a shorter mate, so only trust one found by a search
+        // at least twice as deep as the mate is long.
+        if score >= MATE_THRESHOLD && 2 * (MATE_SCORE - score) <= depth as i32 {
             break;
         }
+
+        if move_clock.soft_limit_reached() {
+            break;
+        }
+    }
+
+    // An infinite or pondering search may only report its move once the GUI
+    // sends "stop" or "ponderhit".
+    while (limits.infinite || limits.is_pondering()) && !stop_signal.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(1));
     }
-    if best_move.is_none() {
-        let legal_moves = game.generate_legal_moves();
-        if !legal_moves.is_empty() {
-            best_move = legal_moves.iter().next().copied(); // pick first legal move
+
+    if let Some(history) = &limits.history {
+        *history.lock().unwrap() = search_helper.history;
+    }
+    lines
+}
+
+/// Searches the root with a narrow window around the previous iteration's score,
+/// widening it on the side that failed until the score falls inside.
+fn aspiration_search(
+    game: &mut Game,
+    depth: u8,
+    previous_score: Option<i32>,
+    stop_signal: &Arc<AtomicBool>,
+    search_helper: &mut Search,
+) -> i32 {
+    let (mut alpha, mut beta) = match previous_score {
+        Some(score)
+            if search_helper.narrow_windows
+                && depth >= ASPIRATION_MIN_DEPTH
+                && score.abs() < MATE_THRESHOLD =>
+        {
+            (
+                (score - ASPIRATION_WINDOW).max(-MATE_SCORE),
+                (score + ASPIRATION_WINDOW).min(MATE_SCORE),
+            )
+        }
+        _ => (-MATE_SCORE, MATE_SCORE),
+    };
+    let mut delta = ASPIRATION_WINDOW;
+
+    loop {
+        let score = search(game, depth, 0, alpha, beta, stop_signal, search_helper);
+        if stop_signal.load(Ordering::Relaxed) {
+            return score;
+        }
+
+        if score <= alpha && alpha > -MATE_SCORE {
+            alpha = (alpha - delta).max(-MATE_SCORE);
+        } else if score >= beta && beta < MATE_SCORE {
+            beta = (beta + delta).min(MATE_SCORE);
         } else {
-            println!("No legal moves found at the end of search!");
+            return score;
         }
+        delta *= 2;
     }
+}
 
-    (best_move, best_score)
+/// Rebuilds a PV by following best moves through the transposition table.
+/// Entries may have been overwritten, so every move is checked for legality.
+fn pv_from_tt(game: &Game, max_len: usize) -> Vec<(usize, usize, Option<Piece>)> {
+    let mut pv = Vec::new();
+    let mut temp_game = game.clone(); // Create a temporary board to walk the PV
+    for _ in 0..max_len {
+        let Some(mv) = temp_game.tt.probe(temp_game.zobrist_hash).and_then(|e| e.best_move) else {
+            break; // Stop if position not in TT or no best move is stored
+        };
+        if !temp_game.generate_legal_moves().iter().any(|m| *m == mv) {
+            break;
+        }
+        pv.push(mv);
+        temp_game.make_move_unchecked(mv.0, mv.1, mv.2);
+    }
+    pv
 }
 
 fn search(
     game: &mut Game,
     depth: u8,
+    ply: usize,
     mut alpha: i32,
     beta: i32,
     stop_signal: &Arc<AtomicBool>,
     search_helper: &mut Search,
 ) -> i32 {
     search_helper.nodes_searched += 1;
+    search_helper.sel_depth = search_helper.sel_depth.max(ply);
+    search_helper.clear_pv(ply);
+    if let Some(limit) = search_helper.node_limit
+        && search_helper.nodes_searched >= limit
+    {
+        stop_signal.store(true, Ordering::Relaxed);
+    }
+
+    if ply >= MAX_PLY - 1 {
+        return eval::eval(game);
+    }
+    if ply == 0 {
+        search_helper.root_depth = depth;
+        search_helper.root_is_white = game.is_white_turn;
+    }
+
+    // A position repeated inside the tree is scored as a draw straight away, the side
+    // that could avoid the repetition will do so if it has anything better.
+    if ply > 0 && game.repetition_count() > 0 {
+        return search_helper.draw_score(game);
+    }
+
+    // The same goes for the fifty-move rule, unless the last move before it ran out mated.
+    if ply > 0 && game.is_fifty_move_draw() {
+        return if game.is_in_check() && game.generate_legal_moves().is_empty() {
+            -MATE_SCORE + ply as i32
+        } else {
+            search_helper.draw_score(game)
+        };
+    }
+
+    // Extensions are only granted while the line is not already far longer than intended.
+    let can_extend = ply < EXTENSION_PLY_FACTOR * search_helper.root_depth as usize;
+
+    let in_check = game.is_in_check();
+    // Check extension, which also keeps us out of quiescence search while in check.
+    let depth = if in_check && can_extend { depth.saturating_add(1) } else { depth };
+
     if depth == 0 {
-        return eval::quiescence_search(game, alpha, beta, search_helper);
+        return eval::quiescence_search(game, ply, alpha, beta, true, search_helper);
     }
 
     if stop_signal.load(Ordering::Relaxed) {
@@ -178,93 +725,283 @@ fn search(
     }
 
     let key = game.zobrist_hash;
-    if let Some(entry) = game.tt.lock().unwrap().probe(key) {
-        if entry.depth >= depth {
-            match entry.flag {
-                Flag::Exact => return entry.score,
-                Flag::LowerBound if entry.score >= beta => return beta,
-                Flag::UpperBound if entry.score <= alpha => return alpha,
-                _ => {}
-            }
+    let excluded_move = search_helper.excluded_move[ply];
+    let tt_entry = game.tt.probe(key);
+    // No cutoffs at the root, the stored move may not be one we are allowed to play,
+    // nor in a singular search, the stored score includes the excluded move.
+    if ply > 0
+        && excluded_move.is_none()
+        && let Some(entry) = tt_entry
+        && entry.depth >= depth
+    {
+        let score = score_from_tt(entry.score, ply);
+        match entry.flag {
+            Flag::Exact => return score,
+            Flag::LowerBound if score >= beta => return beta,
+            Flag::UpperBound if score <= alpha => return alpha,
+            _ => {}
         }
     }
 
-    let in_check = game.is_in_check();
-    if in_check { depth + 1 } else { depth };
+    let is_pv_node = beta - alpha > 1;
+    // Only meaningful out of check, where the side to move could stand pat.
+    let static_eval = if in_check {
+        -MATE_SCORE
+    } else {
+        tt_entry
+            .and_then(|entry| entry.static_eval)
+            .unwrap_or_else(|| eval::eval(game))
+    };
+    let can_prune = !is_pv_node
+        && !in_check
+        && ply > 0
+        && excluded_move.is_none()
+        && alpha.abs() < MATE_THRESHOLD
+        && beta.abs() < MATE_THRESHOLD;
 
-    let mut moves = game.generate_legal_moves();
+    if can_prune
+        && search_helper.pruning.reverse_futility
+        && depth <= RFP_MAX_DEPTH
+        && static_eval - RFP_MARGIN * depth as i32 >= beta
+    {
+        return beta;
+    }
 
-    if moves.is_empty() {
-        return if game.is_in_check() {
-            -MATE_SCORE + pst::ply(game) as i32
-        } else {
-            0
-        };
+    if can_prune
+        && search_helper.pruning.razoring
+        && depth <= RAZORING_MAX_DEPTH
+        && static_eval + RAZORING_MARGIN * depth as i32 <= alpha
+    {
+        let score = eval::quiescence_search(game, ply, alpha, beta, true, search_helper);
+        if score <= alpha {
+            return alpha;
+        }
     }
 
-    let ply = pst::ply(game) as usize;
-    moves.sort_by_cached_key(|m| -(see::score_move(game, *m, ply, search_helper)));
+    if !is_pv_node
+        && !in_check
+        && excluded_move.is_none()
+        && ply > 0
+        && ply + 1 < MAX_PLY
+        && depth >= NMP_MIN_DEPTH
+        && !search_helper.null_moved[ply - 1]
+        && !search_helper.verifying_null_move
+        && beta.abs() < MATE_THRESHOLD
+        && has_non_pawn_material(game)
+        && static_eval >= beta
+    {
+        let reduction = NMP_BASE_REDUCTION + depth / 6;
+        let null_depth = depth.saturating_sub(1 + reduction);
+
+        let undo = game.make_null_move();
+        search_helper.null_moved[ply] = true;
+        search_helper.moved[ply] = None;
+        let null_score = -search(
+            game,
+            null_depth,
+            ply + 1,
+            -beta,
+            -beta + 1,
+            stop_signal,
+            search_helper,
+        );
+        search_helper.null_moved[ply] = false;
+        game.unmake_null_move(undo);
+
+        if stop_signal.load(Ordering::Relaxed) {
+            return 0;
+        }
+
+        if null_score >= beta {
+            if depth < NMP_VERIFICATION_DEPTH {
+                return beta;
+            }
+
+            // Deep cutoffs are only trusted once a normal search without null moves agrees.
+            search_helper.verifying_null_move = true;
+            let verified_score = search(
+                game,
+                depth - reduction,
+                ply,
+                beta - 1,
+                beta,
+                stop_signal,
+                search_helper,
+            );
+            search_helper.verifying_null_move = false;
+
+            if verified_score >= beta {
+                return beta;
+            }
+        }
+    }
+
+    // A root search without its best moves must not overwrite the real root entry.
+    let excludes_root_moves = ply == 0 && !search_helper.excluded_root_moves.is_empty();
+    let skip_tt_store = excludes_root_moves || excluded_move.is_some();
+    let stored_eval = (!in_check).then_some(static_eval);
+
+    // Singular extension: the TT move is extended when every other move fails low
+    // against a margin below its stored score, searched to half the depth.
+    let mut singular_move = None;
+    if ply > 0
+        && can_extend
+        && depth >= SINGULAR_MIN_DEPTH
+        && excluded_move.is_none()
+        && let Some(entry) = tt_entry
+        && let Some(tt_move) = entry.best_move
+        && entry.flag != Flag::UpperBound
+        && entry.depth.saturating_add(SINGULAR_TT_DEPTH_MARGIN) >= depth
+        && entry.score.abs() < MATE_THRESHOLD
+        && game.is_pseudo_legal(tt_move)
+    {
+        let singular_beta = score_from_tt(entry.score, ply) - 2 * depth as i32;
+        search_helper.excluded_move[ply] = Some(tt_move);
+        let score = search(
+            game,
+            (depth - 1) / 2,
+            ply,
+            singular_beta - 1,
+            singular_beta,
+            stop_signal,
+            search_helper,
+        );
+        search_helper.excluded_move[ply] = None;
+
+        if stop_signal.load(Ordering::Relaxed) {
+            return 0;
+        }
+        if score < singular_beta {
+            singular_move = Some(tt_move);
+        }
+    }
 
     let mut best_move = None;
     let mut flag = Flag::UpperBound;
+    let mut quiets_tried = MoveList::new();
+    let mut captures_tried = MoveList::new();
+    let mut legal_moves = 0;
 
-    for (move_count, m) in moves.iter().enumerate() {
-        // Not affected by this
-        let is_quiet = m.2.is_none()
-            && (1u64 << m.1)
-                & if game.is_white_turn {
-                    game.board.black_pieces()
-                } else {
-                    game.board.white_pieces()
-                }
-                == 0;
-
-        let mut reduce = 0;
-        if is_quiet && depth > 2 && move_count > 1 {
-            // Basic reduction from the table
-            let d = (depth as usize).min(MAX_LMR_DEPTH - 1);
-            let mv_idx = move_count.min(MAX_LMR_MOVES - 1);
-            reduce = LMR_TABLE[d][mv_idx];
+    let tt_move = tt_entry.and_then(|entry| entry.best_move);
+    let mut picker = MovePicker::new(game, tt_move, ply, search_helper);
+    while let Some(m) = picker.next(game, ply, search_helper) {
+        if ply == 0
+            && !search_helper.root_moves.is_empty()
+            && !search_helper.root_moves.contains(&m)
+        {
+            continue;
         }
+        if excludes_root_moves && search_helper.excluded_root_moves.contains(&m) {
+            continue;
+        }
+        if excluded_move == Some(m) {
+            continue;
+        }
+
+        // Not affected by this
+        let is_quiet = m.2.is_none() && !is_capture(game, m);
 
         let piece = pst::get_piece_at(game, m.0);
 
+        let is_recapture =
+            !is_quiet && ply > 0 && search_helper.capture_square[ply - 1] == Some(m.1);
+        // A pawn reaching the 7th rank is always passed, nothing can stand in front of it.
+        let is_pawn_to_seventh = piece == Some(Piece::Pawn)
+            && if game.is_white_turn {
+                m.1 / 8 == 6
+            } else {
+                m.1 / 8 == 1
+            };
+        let extension = u8::from(
+            can_extend && (singular_move == Some(m) || is_recapture || is_pawn_to_seventh),
+        );
+        let new_depth = depth - 1 + extension;
+        search_helper.capture_square[ply] = (!is_quiet).then_some(m.1);
+        search_helper.moved[ply] = piece.map(|p| PieceTo::new(p, game.is_white_turn, m.1));
+
         // This is modifying m?
         let undo = game.make_move_unchecked(m.0, m.1, m.2);
 
-        let king_square = if !game.is_white_turn {
-            game.board.white_king
-        } else {
-            game.board.black_king
-        }
-        .trailing_zeros() as usize;
-        if game.board.possible_check(king_square, game.is_white_turn) {
+        if left_king_in_check(game) {
             // King is in check, so this move was illegal. Unmake it and skip.
             game.unmake_move(undo);
             continue;
         }
+        let move_count = legal_moves;
+        legal_moves += 1;
 
-        // Not affected by this
-        let score = if reduce > 0 && depth > 2 {
-            // reduced search
-            let reduced_depth = depth.saturating_sub(reduce);
-            let reduced_score = -search(
-                game,
-                reduced_depth,
-                -alpha - 1,
-                -alpha,
-                stop_signal,
-                search_helper,
-            );
-            if reduced_score > alpha && reduced_score < beta {
-                // re-search at full depth
-                -search(game, depth - 1, -beta, -alpha, stop_signal, search_helper)
-            } else {
-                reduced_score
+        let mut reduce = 0;
+        if is_quiet && depth > 2 && move_count > 1 {
+            // Basic reduction from the table
+            let d = (depth as usize).min(MAX_LMR_DEPTH - 1);
+            let mv_idx = move_count.min(MAX_LMR_MOVES - 1);
+            reduce = LMR_TABLE[d][mv_idx];
+        }
+
+        // Quiet moves that do not give check are skipped once they come late in the list, or
+        // when even a margin over the static eval does not bring them up to alpha.
+        if can_prune && move_count > 0 && is_quiet && !is_pawn_to_seventh && !game.is_in_check() {
+            let late = search_helper.pruning.late_move
+                && depth <= LMP_MAX_DEPTH
+                && move_count >= LMP_BASE_MOVES + (depth as usize).pow(2);
+            let futile = search_helper.pruning.futility
+                && depth <= FUTILITY_MAX_DEPTH
+                && static_eval + FUTILITY_MARGIN * depth as i32 <= alpha;
+            if late || futile {
+                game.unmake_move(undo);
+                continue;
             }
+        }
+
+        if ply == 0
+            && search_helper.report_root_moves
+            && search_helper.time.elapsed() >= CURRMOVE_REPORT_DELAY
+        {
+            println!(
+                "info depth {depth} currmove {} currmovenumber {}",
+                move_to_uci(m),
+                move_count + 1
+            );
+        }
+
+        // Principal variation search: only the first move gets the full window, the rest
+        // are expected to fail low and are searched with a null window around alpha.
+        let score = if move_count == 0 {
+            -search(game, new_depth, ply + 1, -beta, -alpha, stop_signal, search_helper)
         } else {
-            // full-depth search
-            -search(game, depth - 1, -beta, -alpha, stop_signal, search_helper)
+            // Switching narrow windows off searches these with the full window too.
+            let window_beta = if search_helper.narrow_windows { alpha + 1 } else { beta };
+            let mut score = alpha + 1;
+            if reduce > 0 && depth > 2 {
+                // reduced search
+                let reduced_depth = new_depth.saturating_sub(reduce);
+                score = -search(
+                    game,
+                    reduced_depth,
+                    ply + 1,
+                    -window_beta,
+                    -alpha,
+                    stop_signal,
+                    search_helper,
+                );
+            }
+            if score > alpha {
+                // null-window search at full depth
+                score = -search(
+                    game,
+                    new_depth,
+                    ply + 1,
+                    -window_beta,
+                    -alpha,
+                    stop_signal,
+                    search_helper,
+                );
+            }
+            if score > alpha && score < beta && window_beta < beta {
+                // re-search with the full window to get an exact score
+                score = -search(game, new_depth, ply + 1, -beta, -alpha, stop_signal, search_helper);
+            }
+            score
         };
 
         game.unmake_move(undo);
@@ -274,33 +1011,133 @@ fn search(
         }
 
         if score >= beta {
-            game.tt
-                .lock()
-                .unwrap()
-                .store(key, depth, beta, Flag::LowerBound, Some(*m));
+            search_helper.update_cutoff_histories(
+                game,
+                ply,
+                depth,
+                m,
+                &quiets_tried,
+                &captures_tried,
+            );
+            if !skip_tt_store {
+                let tt_score = score_to_tt(beta, ply);
+                game.tt
+                    .store(key, depth, tt_score, Flag::LowerBound, Some(m), stored_eval);
+            }
             return beta;
         }
         if score > alpha {
             alpha = score;
             best_move = Some(m);
             flag = Flag::Exact;
+            search_helper.update_pv(ply, m);
         }
 
-        if is_quiet {
-            if let Some(piece) = piece {
-                search_helper.add_killer_move(ply, *m);
-                search_helper.update_history_score(piece, m.1, depth as i32);
-            }
+        if is_capture(game, m) {
+            captures_tried.add(m.0, m.1, m.2);
+        } else if is_quiet {
+            quiets_tried.add(m.0, m.1, m.2);
         }
     }
 
-    game.tt
-        .lock()
-        .unwrap()
-        .store(key, depth, alpha, flag, best_move.copied());
+    // Moves are only generated as far as needed, so mate and stalemate are recognised here.
+    // With moves excluded at the root or by a singular search there may still be legal ones.
+    if legal_moves == 0 && game.generate_legal_moves().is_empty() {
+        return if in_check {
+            -MATE_SCORE + ply as i32
+        } else {
+            search_helper.draw_score(game)
+        };
+    }
+
+    if !skip_tt_store {
+        game.tt
+            .store(key, depth, score_to_tt(alpha, ply), flag, best_move, stored_eval);
+    }
     alpha
 }
 
+/// True when the move just made leaves the mover's king attacked, so it was not legal.
+fn left_king_in_check(game: &Game) -> bool {
+    let king_square = if !game.is_white_turn {
+        game.board.white_king
+    } else {
+        game.board.black_king
+    }
+    .trailing_zeros() as usize;
+    game.board.possible_check(king_square, game.is_white_turn)
+}
+
+/// True when the side to move has a piece other than pawns and king, outside of which
+/// zugzwang is common enough that passing the turn is no indication of a strong position.
+fn has_non_pawn_material(game: &Game) -> bool {
+    let board = &game.board;
+    let pieces = if game.is_white_turn {
+        board.white_knight | board.white_bishop | board.white_rook | board.white_queen
+    } else {
+        board.black_knight | board.black_bishop | board.black_rook | board.black_queen
+    };
+    pieces != 0
+}
+
+/// Formats a score for `info score`, either `cp <x>` or `mate <y>`, optionally followed by `wdl`.
+fn format_score(score: i32, show_wdl: bool) -> String {
+    let mut text = match mate_in(score) {
+        Some(moves) => format!("mate {moves}"),
+        None => format!("cp {score}"),
+    };
+    if show_wdl {
+        let (win, draw, loss) = win_draw_loss(score);
+        text.push_str(&format!(" wdl {win} {draw} {loss}"));
+    }
+    text
+}
+
+/// Estimates win, draw and loss chances in permille from the side to move's point of view.
+/// A logistic curve on the centipawn score, so an even position is mostly drawn.
+fn win_draw_loss(score: i32) -> (u32, u32, u32) {
+    if score >= MATE_THRESHOLD {
+        return (1000, 0, 0);
+    }
+    if score <= -MATE_THRESHOLD {
+        return (0, 0, 1000);
+    }
+    let expected = |cp: i32| 1000.0 / (1.0 + (-(cp as f64 - 200.0) / 100.0).exp());
+    let win = expected(score).round() as u32;
+    let loss = expected(-score).round() as u32;
+    (win, 1000 - win - loss, loss)
+}
+
+/// Converts a mate score into moves until mate, negative when the side to move is getting mated.
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score >= MATE_THRESHOLD {
+        Some((MATE_SCORE - score + 1) / 2)
+    } else if score <= -MATE_THRESHOLD {
+        Some(-(MATE_SCORE + score + 1) / 2)
+    } else {
+        None
+    }
+}
+
+/// Looks up the expected reply to `best_move` in the transposition table, for when the
+/// search result's PV ends after the first move.
+pub fn find_ponder_move(
+    game: &Game,
+    best_move: (usize, usize, Option<Piece>),
+) -> Option<(usize, usize, Option<Piece>)> {
+    let mut temp_game = game.clone();
+    temp_game.make_move_unchecked(best_move.0, best_move.1, best_move.2);
+
+    let ponder_move = temp_game.tt.probe(temp_game.zobrist_hash)?.best_move?;
+
+    // Guard against hash collisions handing us a move from another position.
+    temp_game
+        .generate_legal_moves()
+        .iter()
+        .any(|m| *m == ponder_move)
+        .then_some(ponder_move)
+}
+
 pub fn move_to_uci(mov: (usize, usize, Option<Piece>)) -> String {
     let from_sq = mov.0;
     let to_sq = mov.1;
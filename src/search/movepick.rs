@@ -0,0 +1,170 @@
+use super::{KillerMove, MAX_PLY, Search, is_capture, see};
+use crate::board::movegen::{generate_pseudo_legal_captures, generate_pseudo_legal_quiets};
+use crate::game::Game;
+use crate::Piece;
+
+type Move = (usize, usize, Option<Piece>);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Stage {
+    TtMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    Countermove,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Hands out the moves of a node one at a time, best first, generating each group of
+/// moves only once the previous one is used up. A beta cutoff on the TT move or a good
+/// capture therefore never pays for generating and sorting the quiet moves.
+///
+/// The order is: TT move, captures that do not lose material by SEE, killers, the
+/// countermove, the remaining quiets by history, and finally the losing captures.
+///
+/// Moves are pseudo-legal, the caller still has to reject those that leave the king in check.
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: KillerMove,
+    killers: [KillerMove; 2],
+    countermove: KillerMove,
+    // Moves of the current stage with their ordering scores, picked from `index` onwards.
+    scored: Vec<(Move, i32)>,
+    index: usize,
+    bad_captures: Vec<(Move, i32)>,
+}
+
+impl MovePicker {
+    pub fn new(game: &Game, tt_move: KillerMove, ply: usize, search_helper: &Search) -> Self {
+        let killers = if ply < MAX_PLY {
+            search_helper.killer_moves[ply]
+        } else {
+            [None; 2]
+        };
+        let countermove = search_helper.previous_moves(ply)[0]
+            .and_then(|prev| search_helper.history.countermove(prev));
+
+        Self {
+            stage: Stage::TtMove,
+            tt_move: tt_move.filter(|mv| game.is_pseudo_legal(*mv)),
+            killers,
+            countermove,
+            scored: Vec::new(),
+            index: 0,
+            bad_captures: Vec::new(),
+        }
+    }
+
+    /// The next move to search, or None once every move has been handed out.
+    pub fn next(&mut self, game: &Game, ply: usize, search_helper: &mut Search) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if self.tt_move.is_some() {
+                        return self.tt_move;
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    self.scored = generate_pseudo_legal_captures(game)
+                        .iter()
+                        .map(|&m| (m, see::score_move(game, m, ply, self.tt_move, search_helper)))
+                        .collect();
+                    self.index = 0;
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => match self.pick_best() {
+                    Some((m, _)) if Some(m) == self.tt_move => {}
+                    // Losing captures and underpromotions are kept for the end, after every quiet move.
+                    Some((m, score))
+                        if m.2.is_some_and(|p| p != Piece::Queen)
+                            || see::static_exchange_exchange(game, m.0, m.1) < 0 =>
+                    {
+                        self.bad_captures.push((m, score));
+                    }
+                    Some((m, _)) => return Some(m),
+                    None => {
+                        self.stage = Stage::Killers;
+                        self.index = 0;
+                    }
+                },
+                Stage::Killers => {
+                    while self.index < 2 {
+                        let killer = self.killers[self.index];
+                        self.index += 1;
+                        // The same move may be stored as both killers.
+                        if let Some(m) = killer
+                            && killer != self.tt_move
+                            && (self.index == 1 || killer != self.killers[0])
+                            && is_playable_quiet(game, m)
+                        {
+                            return Some(m);
+                        }
+                    }
+                    self.stage = Stage::Countermove;
+                }
+                Stage::Countermove => {
+                    self.stage = Stage::GenerateQuiets;
+                    if let Some(m) = self.countermove
+                        && self.countermove != self.tt_move
+                        && !self.killers.contains(&self.countermove)
+                        && is_playable_quiet(game, m)
+                    {
+                        return Some(m);
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    self.stage = Stage::Quiets;
+                    self.scored = generate_pseudo_legal_quiets(game)
+                        .iter()
+                        .filter(|&&m| {
+                            let m = Some(m);
+                            m != self.tt_move && !self.killers.contains(&m) && m != self.countermove
+                        })
+                        .map(|&m| (m, see::score_move(game, m, ply, self.tt_move, search_helper)))
+                        .collect();
+                    self.index = 0;
+                }
+                Stage::Quiets => match self.pick_best() {
+                    Some((m, _)) => return Some(m),
+                    None => {
+                        self.scored = std::mem::take(&mut self.bad_captures);
+                        self.index = 0;
+                        self.stage = Stage::BadCaptures;
+                    }
+                },
+                Stage::BadCaptures => match self.pick_best() {
+                    Some((m, _)) => return Some(m),
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// Moves the highest scoring remaining move to the front and returns it. A full sort is
+    /// not worth it, most nodes cut off after the first few moves.
+    fn pick_best(&mut self) -> Option<(Move, i32)> {
+        let remaining = self.scored.get_mut(self.index..)?;
+        let best = remaining
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, score))| *score)?
+            .0;
+        remaining.swap(0, best);
+        self.index += 1;
+        Some(remaining[0])
+    }
+}
+
+/// Whether a killer or countermove, which were found in other positions, is a quiet move
+/// here. Anything else is either not playable or already handed out with the captures.
+fn is_playable_quiet(game: &Game, mv: Move) -> bool {
+    mv.2.is_none()
+        && game.board.all_pieces() & (1u64 << mv.1) == 0
+        && !is_capture(game, mv)
+        && game.is_pseudo_legal(mv)
+}
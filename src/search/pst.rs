@@ -210,7 +210,3 @@ pub fn get_piece_colour_at(game: &Game, square: usize) -> Option<(Piece, bool)>
 
     None
 }
-
-pub fn ply(game: &Game) -> u8 {
-    game.position_history.len() as u8
-}
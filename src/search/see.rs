@@ -1,4 +1,7 @@
-use crate::{game::Game, search::{eval, get_piece_value, pst, Search, KILLER_MOVE_SCORE, MAX_PLY, PIECE_VALUES}, Piece};
+use crate::{game::Game, search::{eval, get_piece_value, history::PieceTo, is_capture, pst, Search, KILLER_MOVE_SCORE, MAX_PLY, TT_MOVE_SCORE}, Piece};
+
+const CAPTURE_HISTORY_DIVISOR: i32 = 32;
+const QUIET_HISTORY_DIVISOR: i32 = 8;
 
 pub fn static_exchange_exchange(game: &Game, from: usize, to: usize) -> i32 {
     let mut gain = [0i32; 32];
@@ -13,7 +16,7 @@ pub fn static_exchange_exchange(game: &Game, from: usize, to: usize) -> i32 {
     let Some(attacked_piece) = pst::get_piece_at(game, to) else {
         return 0;
     };
-    gain[0] = PIECE_VALUES[attacked_piece as usize];
+    gain[0] = get_piece_value(attacked_piece);
 
     let from_mask = 1u64 << from;
     occupied &= !from_mask; // remove attacker from occupancy
@@ -33,7 +36,7 @@ pub fn static_exchange_exchange(game: &Game, from: usize, to: usize) -> i32 {
         let piece = pst::get_piece_at(game, sq).unwrap();
         depth += 1;
 
-        gain[depth] = PIECE_VALUES[piece as usize] - gain[depth - 1];
+        gain[depth] = get_piece_value(piece) - gain[depth - 1];
 
         occupied &= !(1u64 << sq);
         used_attackers |= 1u64 << sq;
@@ -109,10 +112,15 @@ pub fn score_move(
     game: &Game,
     mov: (usize, usize, Option<Piece>),
     ply: usize,
+    tt_move: Option<(usize, usize, Option<Piece>)>,
     search_helper: &mut Search,
 ) -> i32 {
     let (from, to, promo) = mov;
 
+    if tt_move == Some(mov) {
+        return TT_MOVE_SCORE;
+    }
+
     if let Some(p) = promo {
         return match p {
             Piece::Queen => 20000 + get_piece_value(p),
@@ -121,16 +129,13 @@ pub fn score_move(
         };
     }
 
-    let enemy_pieces = if game.is_white_turn {
-        game.board.black_pieces()
-    } else {
-        game.board.white_pieces()
-    };
-
-    if (1u64 << to) & enemy_pieces != 0 {
+    if is_capture(game, mov) {
         let attacker = pst::get_piece_at(game, from).unwrap_or(Piece::Pawn);
         let victim = pst::get_piece_at(game, to).unwrap_or(Piece::Pawn);
-        return 10000 + get_piece_value(victim) - get_piece_value(attacker);
+        let moved = PieceTo::new(attacker, game.is_white_turn, to);
+        // History only breaks ties between similar captures, MVV-LVA still decides the rest.
+        return 10000 + get_piece_value(victim) - get_piece_value(attacker)
+            + search_helper.history.capture_score(moved, victim) / CAPTURE_HISTORY_DIVISOR;
     }
 
     if ply < MAX_PLY {
@@ -142,8 +147,17 @@ pub fn score_move(
         }
     }
 
+    let previous = search_helper.previous_moves(ply);
+    if let Some(prev) = previous[0]
+        && search_helper.history.countermove(prev) == Some(mov)
+    {
+        return KILLER_MOVE_SCORE;
+    }
+
     if let Some(piece) = pst::get_piece_at(game, from) {
-        return search_helper.history[piece as usize][to];
+        let moved = PieceTo::new(piece, game.is_white_turn, to);
+        // Scaled down so that even the best quiet move stays behind every capture.
+        return search_helper.history.quiet_score(moved, previous) / QUIET_HISTORY_DIVISOR;
     }
 
     0
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+// Assumed number of moves left when the GUI does not send `movestogo`.
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+const MAX_MOVES_TO_GO: u64 = 50;
+
+// Never plan to spend more than this share (in percent) of the clock on one move.
+const MAX_USAGE_PERCENT: u64 = 80;
+const HARD_LIMIT_FACTOR: u64 = 3;
+
+/// Clock parameters sent with `go`, all in milliseconds.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeControl {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: u64,
+    pub binc: u64,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+}
+
+/// Decides how long the engine may think about the current move.
+///
+/// The soft limit is checked between iterations of the iterative deepening,
+/// the hard limit is enforced by the UCI timer thread raising the stop flag.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        Self::infinite()
+    }
+}
+
+impl TimeManager {
+    /// A time manager without limits, for depth-only or infinite searches.
+    pub fn infinite() -> Self {
+        Self {
+            start: Instant::now(),
+            soft_limit: None,
+            hard_limit: None,
+        }
+    }
+
+    /// Computes the soft and hard limits for the side to move.
+    pub fn new(tc: &TimeControl, is_white: bool, move_overhead: u64) -> Self {
+        let start = Instant::now();
+
+        if let Some(movetime) = tc.movetime {
+            let limit = Duration::from_millis(movetime.saturating_sub(move_overhead).max(1));
+            return Self {
+                start,
+                soft_limit: Some(limit),
+                hard_limit: Some(limit),
+            };
+        }
+
+        let (time_left, increment) = if is_white {
+            (tc.wtime, tc.winc)
+        } else {
+            (tc.btime, tc.binc)
+        };
+
+        let Some(time_left) = time_left else {
+            return Self::infinite();
+        };
+
+        let available = time_left.saturating_sub(move_overhead).max(1);
+        let moves_to_go = tc
+            .movestogo
+            .unwrap_or(DEFAULT_MOVES_TO_GO)
+            .clamp(1, MAX_MOVES_TO_GO);
+        let max_usage = (available * MAX_USAGE_PERCENT / 100).max(1);
+
+        let soft = (available / moves_to_go + increment * 3 / 4).clamp(1, max_usage);
+        let hard = (soft * HARD_LIMIT_FACTOR).min(max_usage);
+
+        Self {
+            start,
+            soft_limit: Some(Duration::from_millis(soft)),
+            hard_limit: Some(Duration::from_millis(hard)),
+        }
+    }
+
+    /// Starts the clock again, for a ponder search that became a timed one.
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn soft_limit(&self) -> Option<Duration> {
+        self.soft_limit
+    }
+
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard_limit
+    }
+
+    /// Returns true once the search should not start another iteration.
+    pub fn soft_limit_reached(&self) -> bool {
+        self.soft_limit.is_some_and(|limit| self.elapsed() >= limit)
+    }
+}
@@ -1,7 +1,27 @@
-use std::collections::HashMap;
+use std::alloc::{Layout, alloc_zeroed, dealloc, handle_alloc_error};
+use std::mem::{align_of, size_of};
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU8, AtomicU16, AtomicU64, Ordering};
 
+use super::MATE_THRESHOLD;
 use crate::Piece;
 
+pub const DEFAULT_HASH_MB: usize = 128;
+
+const ENTRIES_PER_BUCKET: usize = 6;
+const GENERATION_MASK: u8 = 0x3F;
+
+// Layout of the packed data word, from the least significant bit:
+// move (16) | score (16) | static eval (16) | depth (8) | generation (6) + bound (2)
+const SCORE_SHIFT: u32 = 16;
+const EVAL_SHIFT: u32 = 32;
+const DEPTH_SHIFT: u32 = 48;
+const GEN_BOUND_SHIFT: u32 = 56;
+
+// Marks an entry stored without a static evaluation.
+const NO_EVAL: i16 = i16::MIN;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Flag {
     Exact,
@@ -9,6 +29,26 @@ pub enum Flag {
     UpperBound,
 }
 
+impl Flag {
+    // 0 is reserved for empty entries.
+    fn to_bits(self) -> u8 {
+        match self {
+            Flag::Exact => 1,
+            Flag::LowerBound => 2,
+            Flag::UpperBound => 3,
+        }
+    }
+
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            1 => Some(Flag::Exact),
+            2 => Some(Flag::LowerBound),
+            3 => Some(Flag::UpperBound),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct TTEntry {
     pub key: u64,
@@ -16,51 +56,163 @@ pub struct TTEntry {
     pub score: i32, // Eval
     pub flag: Flag,
     pub best_move: Option<(usize, usize, Option<Piece>)>,
+    pub static_eval: Option<i32>,
 }
 
-#[derive(Clone, Debug)]
+/// Six entries in one cache line. Entry `i` is `data[i]` verified by `checks[i]`, kept in
+/// separate arrays so the 16-bit checks need no padding.
+///
+/// The bucket index comes from the high bits of the key, the check from the low 16 bits
+/// XORed with the data word folded to 16 bits. An entry torn by two threads writing at
+/// once therefore fails verification on probe like any other key mismatch.
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket {
+    data: [AtomicU64; ENTRIES_PER_BUCKET],
+    checks: [AtomicU16; ENTRIES_PER_BUCKET],
+}
+
+const _: () = assert!(size_of::<Bucket>() == 64);
+
+/// A fixed-size, lock-free transposition table shared by every search thread.
 pub struct TranspositionTable {
-    table: HashMap<u64, TTEntry>,
-    capacity: u64,
+    buckets: BucketArray,
+    generation: AtomicU8,
 }
 
 impl TranspositionTable {
     pub fn new() -> Self {
+        Self::with_size_mb(DEFAULT_HASH_MB)
+    }
+
+    /// Creates a table that uses `mb` megabytes.
+    pub fn with_size_mb(mb: usize) -> Self {
+        let bucket_count = (mb * 1024 * 1024 / size_of::<Bucket>()).max(1);
         Self {
-            table: HashMap::with_capacity(2000000),
-            capacity: 2000000,
+            buckets: BucketArray::zeroed(bucket_count),
+            generation: AtomicU8::new(0),
         }
     }
 
-    pub fn with_capacity(capacity: u64) -> Self {
-        TranspositionTable {
-            table: HashMap::with_capacity(capacity as usize),
-            capacity,
-        }
+    /// Ages every entry, called once at the start of each search.
+    pub fn new_search(&self) {
+        let next = (self.generation.load(Ordering::Relaxed) + 1) & GENERATION_MASK;
+        self.generation.store(next, Ordering::Relaxed);
     }
 
-    pub fn store(&mut self, key: u64, depth: u8, score: i32, flag: Flag, best_move: Option<(usize, usize, Option<Piece>)>) {
-        let entry = TTEntry {
-            key,
-            depth,
-            score,
-            flag,
-            best_move,
-        };
-        self.table.insert(key % self.capacity, entry);
+    pub fn store(
+        &self,
+        key: u64,
+        depth: u8,
+        score: i32,
+        flag: Flag,
+        best_move: Option<(usize, usize, Option<Piece>)>,
+        static_eval: Option<i32>,
+    ) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = &self.buckets[self.index(key)];
+
+        // Prefer the slot already holding this position, otherwise evict the
+        // entry that is shallowest once its age is taken into account.
+        let mut replace = 0;
+        let mut replace_value = i32::MAX;
+        let mut previous = None;
+        for i in 0..ENTRIES_PER_BUCKET {
+            let data = bucket.data[i].load(Ordering::Relaxed);
+            if data != 0 && bucket.checks[i].load(Ordering::Relaxed) == check(key, data) {
+                replace = i;
+                previous = Some(data);
+                break;
+            }
+
+            let value = if data == 0 {
+                i32::MIN
+            } else {
+                let age = generation.wrapping_sub(gen_bound(data) >> 2) & GENERATION_MASK;
+                stored_depth(data) as i32 - 8 * age as i32
+            };
+            if value < replace_value {
+                replace = i;
+                replace_value = value;
+            }
+        }
+
+        let mut packed_move = best_move.map_or(0, pack_move);
+        if let Some(old) = previous {
+            // Keep a deeper entry for the same position unless the new one is exact.
+            if flag != Flag::Exact && depth.saturating_add(2) < stored_depth(old) {
+                return;
+            }
+            if packed_move == 0 {
+                packed_move = old & 0xFFFF;
+            }
+        }
+
+        let eval = static_eval
+            .map_or(NO_EVAL, |e| e.clamp(-i16::MAX as i32, i16::MAX as i32) as i16);
+        let score = score.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        let data = packed_move
+            | (score as u16 as u64) << SCORE_SHIFT
+            | (eval as u16 as u64) << EVAL_SHIFT
+            | (depth as u64) << DEPTH_SHIFT
+            | (((generation << 2) | flag.to_bits()) as u64) << GEN_BOUND_SHIFT;
+
+        bucket.data[replace].store(data, Ordering::Relaxed);
+        bucket.checks[replace].store(check(key, data), Ordering::Relaxed);
     }
 
     pub fn probe(&self, key: u64) -> Option<TTEntry> {
-        if let Some(entry) = self.table.get(&(key % self.capacity)){
-            if entry.key == key {
-                return Some(*entry);
+        let bucket = &self.buckets[self.index(key)];
+        for i in 0..ENTRIES_PER_BUCKET {
+            let data = bucket.data[i].load(Ordering::Relaxed);
+            if data == 0 || bucket.checks[i].load(Ordering::Relaxed) != check(key, data) {
+                continue;
             }
+
+            let flag = Flag::from_bits(gen_bound(data) & 0b11)?;
+            let eval = (data >> EVAL_SHIFT) as u16 as i16;
+            return Some(TTEntry {
+                key,
+                depth: stored_depth(data),
+                score: (data >> SCORE_SHIFT) as u16 as i16 as i32,
+                flag,
+                best_move: unpack_move(data as u16),
+                static_eval: (eval != NO_EVAL).then_some(eval as i32),
+            });
         }
         None
     }
 
-    pub fn clear(&mut self) {
-        self.table.clear();
+    pub fn clear(&self) {
+        for bucket in self.buckets.iter() {
+            for i in 0..ENTRIES_PER_BUCKET {
+                bucket.data[i].store(0, Ordering::Relaxed);
+                bucket.checks[i].store(0, Ordering::Relaxed);
+            }
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    /// Permille of sampled entries written during the current search, as reported by `info hashfull`.
+    pub fn hashfull(&self) -> usize {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = self.buckets.iter().take(1000 / ENTRIES_PER_BUCKET);
+        let sampled = sample.len() * ENTRIES_PER_BUCKET;
+
+        let used = sample
+            .flat_map(|bucket| bucket.data.iter())
+            .filter(|entry| {
+                let data = entry.load(Ordering::Relaxed);
+                data != 0 && gen_bound(data) >> 2 == generation
+            })
+            .count();
+
+        used * 1000 / sampled
+    }
+
+    fn index(&self, key: u64) -> usize {
+        // Maps the key onto the table without requiring a power of two size.
+        ((key as u128 * self.buckets.len() as u128) >> 64) as usize
     }
 }
 
@@ -69,3 +221,120 @@ impl Default for TranspositionTable {
         Self::new()
     }
 }
+
+/// Mate scores are relative to the root while searching but must be stored relative
+/// to the node, otherwise a mate found at one ply is misreported when the position
+/// is reached again at another.
+pub fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score + ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// Inverse of [`score_to_tt`], converting a stored score back to one relative to the root.
+pub fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score - ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// A zeroed, cache line aligned array of buckets.
+///
+/// An all-zero bucket is a valid empty bucket, so the allocator can hand us fresh zeroed
+/// pages instead of us initialising hundreds of megabytes by hand. The system allocator
+/// only does that for small alignments, for the 64 bytes a bucket wants it allocates and
+/// then clears the memory itself. So we allocate with the default alignment plus some
+/// slack and align the buckets within it.
+struct BucketArray {
+    ptr: NonNull<Bucket>,
+    len: usize,
+    allocation: NonNull<u8>,
+    layout: Layout,
+}
+
+// The buckets are only ever accessed through atomics.
+unsafe impl Send for BucketArray {}
+unsafe impl Sync for BucketArray {}
+
+impl BucketArray {
+    fn zeroed(len: usize) -> Self {
+        let slack = align_of::<Bucket>() - 1;
+        let layout = Layout::from_size_align(len * size_of::<Bucket>() + slack, 16)
+            .expect("transposition table too large");
+        unsafe {
+            let Some(allocation) = NonNull::new(alloc_zeroed(layout)) else {
+                handle_alloc_error(layout);
+            };
+            let offset = allocation.as_ptr().align_offset(align_of::<Bucket>());
+            let ptr = NonNull::new_unchecked(allocation.as_ptr().add(offset).cast::<Bucket>());
+            Self {
+                ptr,
+                len,
+                allocation,
+                layout,
+            }
+        }
+    }
+}
+
+impl Deref for BucketArray {
+    type Target = [Bucket];
+
+    fn deref(&self) -> &[Bucket] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for BucketArray {
+    fn drop(&mut self) {
+        // Buckets hold nothing but atomics, there is nothing to drop in place.
+        unsafe { dealloc(self.allocation.as_ptr(), self.layout) }
+    }
+}
+
+/// The 16 bits stored to verify that `data` belongs to `key`.
+fn check(key: u64, data: u64) -> u16 {
+    (key ^ data ^ data >> 16 ^ data >> 32 ^ data >> 48) as u16
+}
+
+fn stored_depth(data: u64) -> u8 {
+    (data >> DEPTH_SHIFT) as u8
+}
+
+fn gen_bound(data: u64) -> u8 {
+    (data >> GEN_BOUND_SHIFT) as u8
+}
+
+fn pack_move(mv: (usize, usize, Option<Piece>)) -> u64 {
+    let promo = match mv.2 {
+        Some(Piece::Queen) => 1,
+        Some(Piece::Rook) => 2,
+        Some(Piece::Bishop) => 3,
+        Some(Piece::Knight) => 4,
+        _ => 0,
+    };
+    (mv.0 as u64) | (mv.1 as u64) << 6 | promo << 12
+}
+
+fn unpack_move(bits: u16) -> Option<(usize, usize, Option<Piece>)> {
+    // A1 to A1 is never a legal move, so an all-zero move means "no move".
+    if bits == 0 {
+        return None;
+    }
+    let promo = match bits >> 12 {
+        1 => Some(Piece::Queen),
+        2 => Some(Piece::Rook),
+        3 => Some(Piece::Bishop),
+        4 => Some(Piece::Knight),
+        _ => None,
+    };
+    Some(((bits & 0x3F) as usize, ((bits >> 6) & 0x3F) as usize, promo))
+}
@@ -1,26 +1,63 @@
 use crate::game::Game;
+use crate::search::history::History;
+use crate::search::{Pruning, SearchLimits, find_ponder_move, move_to_uci};
+use crate::search::time::{TimeControl, TimeManager};
+use crate::search::tt::{DEFAULT_HASH_MB, TranspositionTable};
+use crate::utils::bench::{DEFAULT_BENCH_DEPTH, run_bench};
 use std::io::{self, BufRead};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
+const DEFAULT_MOVE_OVERHEAD: u64 = 300;
+
+// Every parameter `go` may carry, used to find where the `searchmoves` list ends.
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves", "ponder", "wtime", "btime", "winc", "binc", "movestogo", "depth", "nodes",
+    "mate", "movetime", "infinite",
+];
+
 /// Encapsulates the UCI state, including the game and a dedicated search thread.
 struct Uci {
     game: Game,
+    // Shared with every game we search, so it survives "position" and is only cleared on request.
+    tt: Arc<TranspositionTable>,
     stop_signal: Arc<AtomicBool>,
     search_thread: Option<JoinHandle<()>>,
     search_id: Arc<AtomicU64>,
+    move_overhead: u64,
+    threads: usize,
+    show_wdl: bool,
+    multi_pv: usize,
+    contempt: i32,
+    pruning: Pruning,
+    // Move ordering history, kept between searches and only cleared by "ucinewgame".
+    history: Arc<Mutex<History>>,
+    // Set while a "go ponder" search runs, together with the hard limit to arm on "ponderhit".
+    ponder_flag: Option<Arc<AtomicBool>>,
+    ponder_hard_limit: Option<Duration>,
 }
 
 impl Uci {
     /// Creates a new Uci instance.
     fn new() -> Self {
+        let game = Game::new();
         Uci {
-            game: Game::new(),
+            tt: Arc::clone(&game.tt),
+            game,
             stop_signal: Arc::new(AtomicBool::new(false)),
             search_thread: None,
             search_id: Arc::new(AtomicU64::new(0)),
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            threads: 1,
+            show_wdl: false,
+            multi_pv: 1,
+            contempt: 0,
+            pruning: Pruning::default(),
+            history: Arc::new(Mutex::new(History::new())),
+            ponder_flag: None,
+            ponder_hard_limit: None,
         }
     }
 
@@ -31,7 +68,8 @@ impl Uci {
 
         loop {
             let mut input = String::new();
-            if reader.read_line(&mut input).is_err() {
+            // The GUI closing our input is as good as "quit".
+            if !matches!(reader.read_line(&mut input), Ok(n) if n > 0) {
                 break;
             }
 
@@ -44,7 +82,9 @@ impl Uci {
                     "setoption" => self.handle_setoption(&tokens),
                     "position" => self.handle_position(&tokens),
                     "go" => self.handle_go(&tokens),
+                    "ponderhit" => self.handle_ponderhit(),
                     "stop" => self.handle_stop(),
+                    "bench" => self.handle_bench(&tokens),
                     "quit" => break,
                     _ => {} // Ignore unknown commands
                 }
@@ -57,9 +97,18 @@ impl Uci {
         println!("id name SparroweEngine");
         println!("id author Sparrowe");
         // Advertise supported UCI options
-        println!("option name Move Overhead type spin default 300 min 0 max 1000");
+        println!("option name Move Overhead type spin default {DEFAULT_MOVE_OVERHEAD} min 0 max 1000");
         println!("option name Threads type spin default 1 min 1 max 128");
-        println!("option name Hash type spin default 128 min 1 max 2048");
+        println!("option name Hash type spin default {DEFAULT_HASH_MB} min 1 max 2048");
+        println!("option name Clear Hash type button");
+        println!("option name Ponder type check default false");
+        println!("option name MultiPV type spin default 1 min 1 max 256");
+        println!("option name Contempt type spin default 0 min -100 max 100");
+        println!("option name UCI_ShowWDL type check default false");
+        println!("option name ReverseFutilityPruning type check default true");
+        println!("option name Razoring type check default true");
+        println!("option name FutilityPruning type check default true");
+        println!("option name LateMovePruning type check default true");
         println!("uciok");
     }
 
@@ -68,24 +117,73 @@ impl Uci {
         println!("readyok");
     }
 
-    /// Resets the game to the starting position.
+    /// Resets the game to the starting position and forgets everything learned about the last one.
     fn handle_new_game(&mut self) {
         self.handle_stop(); // Stop any thinking before starting a new game
-        self.game = Game::new();
+        self.tt.clear();
+        *self.history.lock().unwrap() = History::new();
+        self.set_game(Game::new());
+    }
+
+    /// Replaces the current game, keeping the shared transposition table.
+    fn set_game(&mut self, mut game: Game) {
+        game.tt = Arc::clone(&self.tt);
+        self.game = game;
     }
 
     /// Handles UCI options set by the GUI.
     fn handle_setoption(&mut self, tokens: &[&str]) {
-        if let (Some(&"name"), Some(name), Some(&"value"), Some(value)) =
-            (tokens.get(1), tokens.get(2), tokens.get(3), tokens.get(4))
-        {
-            if name == &"Hash" {
-                if let Ok(mb) = value.parse::<u64>() {
-                    // This is where you would resize your transposition table.
-                    // For example: self.game.tt.resize(mb);
-                    eprintln!("info string Hash size set to {mb} MB");
+        // Option names may contain spaces, e.g. "setoption name Move Overhead value 100"
+        let Some(name_start) = tokens.iter().position(|&t| t == "name") else {
+            return;
+        };
+        let value_start = tokens.iter().position(|&t| t == "value");
+        let name = tokens[name_start + 1..value_start.unwrap_or(tokens.len())].join(" ");
+        let value = value_start.map(|i| tokens[i + 1..].join(" ")).unwrap_or_default();
+
+        match name.as_str() {
+            "Hash" => {
+                if let Ok(mb) = value.parse::<usize>() {
+                    let mb = mb.clamp(1, 2048);
+                    self.handle_stop();
+                    self.tt = Arc::new(TranspositionTable::with_size_mb(mb));
+                    self.game.tt = Arc::clone(&self.tt);
+                    println!("info string Hash size set to {mb} MB");
                 }
             }
+            "Clear Hash" => {
+                self.handle_stop();
+                self.tt.clear();
+            }
+            "Move Overhead" => {
+                if let Ok(ms) = value.parse::<u64>() {
+                    self.move_overhead = ms.min(1000);
+                }
+            }
+            "Threads" => {
+                if let Ok(threads) = value.parse::<usize>() {
+                    self.handle_stop();
+                    self.threads = threads.clamp(1, 128);
+                }
+            }
+            "MultiPV" => {
+                if let Ok(lines) = value.parse::<usize>() {
+                    self.multi_pv = lines.clamp(1, 256);
+                }
+            }
+            "Contempt" => {
+                if let Ok(cp) = value.parse::<i32>() {
+                    self.contempt = cp.clamp(-100, 100);
+                }
+            }
+            "UCI_ShowWDL" => self.show_wdl = value == "true",
+            "ReverseFutilityPruning" => self.pruning.reverse_futility = value == "true",
+            "Razoring" => self.pruning.razoring = value == "true",
+            "FutilityPruning" => self.pruning.futility = value == "true",
+            "LateMovePruning" => self.pruning.late_move = value == "true",
+            // Pondering is driven entirely by "go ponder", nothing to configure.
+            "Ponder" => {}
+            _ => {}
         }
     }
 
@@ -93,7 +191,7 @@ impl Uci {
     fn handle_position(&mut self, tokens: &[&str]) {
         let mut current_index = 1;
         if tokens.get(current_index) == Some(&"startpos") {
-            self.game = Game::new();
+            self.set_game(Game::new());
             current_index += 1;
         } else if tokens.get(current_index) == Some(&"fen") {
             current_index += 1;
@@ -103,8 +201,13 @@ impl Uci {
                 .cloned()
                 .collect();
 
-            if let Ok(new_game) = Game::from_fen(&fen_parts.join(" ")) {
-                self.game = new_game;
+            match Game::from_fen(&fen_parts.join(" ")) {
+                Ok(new_game) => self.set_game(new_game),
+                Err(e) => {
+                    // Moves meant for that position make no sense on the old one either.
+                    println!("info string invalid FEN: {e}");
+                    return;
+                }
             }
             current_index += fen_parts.len();
         }
@@ -112,9 +215,14 @@ impl Uci {
         if tokens.get(current_index) == Some(&"moves") {
             current_index += 1;
             for move_str in &tokens[current_index..] {
-                if let Some((from, to, promo)) = crate::game::Game::parse_move(move_str) {
-                    self.game.make_move(from, to, promo);
-                }
+                let Some(mv) = Game::parse_move(move_str)
+                    .filter(|mv| self.game.generate_legal_moves().iter().any(|m| m == mv))
+                else {
+                    println!("info string illegal move {move_str}, ignoring the rest");
+                    break;
+                };
+                // The unchecked move keeps the hash and the history for repetitions up to date.
+                self.game.make_move_unchecked(mv.0, mv.1, mv.2);
             }
         }
     }
@@ -123,58 +231,143 @@ impl Uci {
     fn handle_go(&mut self, tokens: &[&str]) {
         self.handle_stop();
 
-        // --- PARSE THE DEPTH HERE ---
-        let depth = Self::find_token_value(tokens, "depth").map_or(u8::MAX, |d| d as u8);
+        let depth = Self::find_token_value(tokens, "depth")
+            .map_or(u8::MAX, |d| d.min(u8::MAX as u64) as u8);
+        let infinite = tokens.contains(&"infinite");
+        let ponder = tokens.contains(&"ponder");
 
-        let time_to_think = 60_000;
+        let time_control = TimeControl {
+            wtime: Self::find_token_value(tokens, "wtime"),
+            btime: Self::find_token_value(tokens, "btime"),
+            winc: Self::find_token_value(tokens, "winc").unwrap_or(0),
+            binc: Self::find_token_value(tokens, "binc").unwrap_or(0),
+            movestogo: Self::find_token_value(tokens, "movestogo"),
+            movetime: Self::find_token_value(tokens, "movetime"),
+        };
+        // "go infinite" ignores any clock information and runs until "stop".
+        let time_manager = if infinite {
+            TimeManager::infinite()
+        } else {
+            TimeManager::new(&time_control, self.game.is_white_turn, self.move_overhead)
+        };
+
+        let limits = SearchLimits {
+            depth,
+            nodes: Self::find_token_value(tokens, "nodes"),
+            mate: Self::find_token_value(tokens, "mate").map(|m| m.min(u8::MAX as u64) as u8),
+            infinite,
+            search_moves: Self::parse_search_moves(tokens),
+            time: time_manager,
+            ponder: ponder.then(|| Arc::new(AtomicBool::new(true))),
+            threads: self.threads,
+            show_wdl: self.show_wdl,
+            multi_pv: self.multi_pv,
+            contempt: self.contempt,
+            pruning: self.pruning,
+            narrow_windows: true,
+            history: Some(Arc::clone(&self.history)),
+        };
 
-        // ... (Your existing timer logic) ...
         self.search_id.fetch_add(1, Ordering::Relaxed);
-        let current_search_id = self.search_id.load(Ordering::Relaxed);
-        let search_id_clone = Arc::clone(&self.search_id);
         self.stop_signal.store(false, Ordering::Relaxed);
-        let stop_clone = Arc::clone(&self.stop_signal);
-        thread::spawn(move || {
-            thread::sleep(Duration::from_millis(time_to_think));
-            if search_id_clone.load(Ordering::Relaxed) == current_search_id {
-                stop_clone.store(true, Ordering::Relaxed);
-            }
-        });
+
+        // The hard limit is enforced here, the soft limit is checked by the search itself.
+        // While pondering our clock is not running, so the timer is only armed on "ponderhit".
+        if ponder {
+            self.ponder_flag = limits.ponder.clone();
+            self.ponder_hard_limit = limits.time.hard_limit();
+        } else if let Some(hard_limit) = limits.time.hard_limit() {
+            self.start_timer(hard_limit);
+        }
 
         let mut game_clone = self.game.clone();
         let stop_clone = Arc::clone(&self.stop_signal);
 
         self.search_thread = Some(thread::spawn(move || {
-            // --- USE THE PARSED `depth` VARIABLE ---
-            let (best_move, _) = crate::search::find_best_move(&mut game_clone, depth, &stop_clone);
-
-            if let Some((from, to, promo)) = best_move {
-                let move_str = format!(
-                    "{}{}{}",
-                    game_clone.square_index_to_coord(from),
-                    game_clone.square_index_to_coord(to),
-                    game_clone.promo_to_char(promo)
-                );
-                println!("bestmove {move_str}");
+            let result = crate::search::find_best_move(&mut game_clone, &limits, &stop_clone);
+
+            if let Some(best_move) = result.best_move {
+                let ponder_move = match result.pv.get(1) {
+                    Some(&reply) if result.pv[0] == best_move => Some(reply),
+                    _ => find_ponder_move(&game_clone, best_move),
+                };
+                match ponder_move {
+                    Some(ponder_move) => println!(
+                        "bestmove {} ponder {}",
+                        move_to_uci(best_move),
+                        move_to_uci(ponder_move)
+                    ),
+                    None => println!("bestmove {}", move_to_uci(best_move)),
+                }
             } else {
                 println!("bestmove 0000");
             }
         }));
     }
+
+    /// The opponent played the expected move, so the ponder search becomes a normal timed one.
+    fn handle_ponderhit(&mut self) {
+        if let Some(flag) = self.ponder_flag.take() {
+            flag.store(false, Ordering::Relaxed);
+            if let Some(hard_limit) = self.ponder_hard_limit.take() {
+                self.start_timer(hard_limit);
+            }
+        }
+    }
+
+    /// Raises the stop signal after `limit`, unless a newer search has started by then.
+    fn start_timer(&self, limit: Duration) {
+        let current_search_id = self.search_id.load(Ordering::Relaxed);
+        let search_id_clone = Arc::clone(&self.search_id);
+        let stop_clone = Arc::clone(&self.stop_signal);
+        thread::spawn(move || {
+            thread::sleep(limit);
+            if search_id_clone.load(Ordering::Relaxed) == current_search_id {
+                stop_clone.store(true, Ordering::Relaxed);
+            }
+        });
+    }
     /// Stops the currently running search and waits for it to terminate.
     fn handle_stop(&mut self) {
+        self.ponder_flag = None;
+        self.ponder_hard_limit = None;
         self.stop_signal.store(true, Ordering::Relaxed);
         if let Some(handle) = self.search_thread.take() {
             handle.join().unwrap();
         }
     }
 
+    /// Runs the fixed bench, "bench <depth>" overrides the default depth.
+    fn handle_bench(&mut self, tokens: &[&str]) {
+        self.handle_stop();
+        let depth = tokens
+            .get(1)
+            .and_then(|d| d.parse::<u8>().ok())
+            .unwrap_or(DEFAULT_BENCH_DEPTH);
+        run_bench(depth);
+    }
+
+    /// Collects the moves following "searchmoves" up to the next `go` parameter.
+    fn parse_search_moves(tokens: &[&str]) -> Vec<(usize, usize, Option<crate::Piece>)> {
+        let Some(start) = tokens.iter().position(|&s| s == "searchmoves") else {
+            return Vec::new();
+        };
+
+        tokens[start + 1..]
+            .iter()
+            .take_while(|s| !GO_KEYWORDS.contains(s))
+            .filter_map(|s| Game::parse_move(s))
+            .collect()
+    }
+
     fn find_token_value(tokens: &[&str], token: &str) -> Option<u64> {
+        // Some GUIs send negative clock values once the flag has fallen, treat those as 0.
         tokens
             .iter()
             .position(|&s| s == token)
             .and_then(|i| tokens.get(i + 1))
-            .and_then(|s| s.parse().ok())
+            .and_then(|s| s.parse::<i64>().ok())
+            .map(|v| v.max(0) as u64)
     }
 }
 
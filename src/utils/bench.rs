@@ -0,0 +1,44 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+
+use crate::Game;
+use crate::search::{SearchLimits, find_best_move};
+
+pub const DEFAULT_BENCH_DEPTH: u8 = 4;
+
+// A fixed mix of opening, middlegame and endgame positions. Keep this list stable,
+// the total node count is used to tell whether a change alters the search.
+pub const BENCH_POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8",
+    "r2q1rk1/1b2bppp/p2ppn2/1p6/3NP3/1BN1B3/PPP2PPP/R2Q1RK1 w - - 0 12",
+    "2r2rk1/pp1bqppp/2n1p3/3pP3/3P4/P1PB1N2/5PPP/R2Q1RK1 w - - 1 16",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4kpp1/3p4/p6P/2B4b/6P1/6K1 w - - 1 48",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+];
+
+/// Searches every bench position to `depth` and prints the node count and speed.
+/// Returns the total number of nodes, which only changes when the search does.
+pub fn run_bench(depth: u8) -> u64 {
+    let start = Instant::now();
+    let mut total_nodes = 0;
+
+    for (index, fen) in BENCH_POSITIONS.iter().enumerate() {
+        let mut game = Game::from_fen(fen).expect("bench positions are valid FENs");
+        let stop_signal = Arc::new(AtomicBool::new(false));
+
+        println!("\nPosition: {}/{} {fen}", index + 1, BENCH_POSITIONS.len());
+        let result = find_best_move(&mut game, &SearchLimits::depth(depth), &stop_signal);
+        total_nodes += result.nodes;
+    }
+
+    let elapsed = start.elapsed().as_millis() as u64;
+    println!("\n===========================");
+    println!("Total time (ms) : {elapsed}");
+    println!("Nodes searched  : {total_nodes}");
+    println!("Nodes/second    : {}", total_nodes * 1000 / elapsed.max(1));
+    total_nodes
+}
@@ -1 +1,2 @@
+pub mod bench;
 pub mod test_runner;
\ No newline at end of file
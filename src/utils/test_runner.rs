@@ -1,9 +1,17 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader};
 use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
 use std::time::Instant;
 
 use crate::Game;
+use crate::game::epd::Epd;
+use crate::search::time::{TimeControl, TimeManager};
+use crate::search::tt::TranspositionTable;
+use crate::search::{SearchLimits, find_best_move, mate_in};
 
 
 /// Reads the test file line by line and executes the perft tests.
@@ -74,4 +82,151 @@ pub fn run_tests<P: AsRef<Path>>(path: P) -> io::Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Size of the table shared by every position of a suite, which is cleared between them.
+const SUITE_HASH_MB: usize = 32;
+
+/// Depth a position is searched to when neither the limits nor its `acd` bound the search.
+pub const DEFAULT_SUITE_DEPTH: u8 = 10;
+
+/// How long the engine may search each position of a tactical suite. Unset limits are not
+/// applied, without a depth each position is searched to its `acd` depth if it has one.
+/// A position with no limit at all is searched to `DEFAULT_SUITE_DEPTH`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SuiteLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    /// Milliseconds per position.
+    pub movetime: Option<u64>,
+}
+
+/// Outcome of a tactical suite run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SuiteReport {
+    pub solved: usize,
+    /// Ids (or line numbers when a position has no id) of the positions that were missed.
+    pub failed: Vec<String>,
+    /// Positions with no `bm`, `am` or `dm` to check the engine's answer against.
+    pub skipped: usize,
+}
+
+/// Searches every position of an EPD file and reports which ones the engine solves.
+pub fn run_epd_suite<P: AsRef<Path>>(path: P, limits: SuiteLimits) -> io::Result<SuiteReport> {
+    let text = fs::read_to_string(path)?;
+    let positions =
+        Epd::parse_all(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(run_epd_positions(&positions, limits))
+}
+
+/// Searches each position within `limits`. A position is solved when the engine plays one
+/// of its `bm` moves, none of its `am` moves and finds a mate at least as short as its `dm`.
+pub fn run_epd_positions(positions: &[Epd], limits: SuiteLimits) -> SuiteReport {
+    let mut report = SuiteReport::default();
+    let start_time = Instant::now();
+    // Every parsed position owns a table of its own, searching them all with one keeps
+    // the memory down and leaves the positions as they were parsed.
+    let tt = Arc::new(TranspositionTable::with_size_mb(SUITE_HASH_MB));
+
+    for (index, epd) in positions.iter().enumerate() {
+        let name = epd.id.clone().unwrap_or_else(|| format!("#{}", index + 1));
+        if epd.best_moves.is_empty() && epd.avoid_moves.is_empty() && epd.direct_mate.is_none() {
+            println!("[{name}] \x1b[93mSKIP\x1b[0m: nothing to check");
+            report.skipped += 1;
+            continue;
+        }
+
+        let mut game = epd.game.clone();
+        tt.clear();
+        game.tt = Arc::clone(&tt);
+        let tc = TimeControl {
+            movetime: limits.movetime,
+            ..TimeControl::default()
+        };
+        let unbounded = limits.nodes.is_none() && limits.movetime.is_none();
+        let depth = limits
+            .depth
+            .or(epd.analysis_depth.map(|d| d.min(u8::MAX as u32) as u8))
+            .unwrap_or(if unbounded { DEFAULT_SUITE_DEPTH } else { u8::MAX });
+        let search_limits = SearchLimits {
+            depth,
+            nodes: limits.nodes,
+            mate: epd.direct_mate.map(|n| n.min(u8::MAX as u32) as u8),
+            time: TimeManager::new(&tc, game.is_white_turn, 0),
+            ..SearchLimits::default()
+        };
+
+        // The search only checks the soft limit between iterations, so a timer raises the
+        // stop flag at the hard limit. Dropping the sender cancels it once the search is done.
+        let stop_signal = Arc::new(AtomicBool::new(false));
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        if let Some(limit) = search_limits.time.hard_limit() {
+            let stop = Arc::clone(&stop_signal);
+            thread::spawn(move || {
+                if cancelled.recv_timeout(limit) == Err(mpsc::RecvTimeoutError::Timeout) {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+
+        let start = Instant::now();
+        let result = find_best_move(&mut game, &search_limits, &stop_signal);
+        drop(cancel);
+        let duration = start.elapsed();
+
+        let played = result.best_move;
+        let mate_found = epd
+            .direct_mate
+            .is_none_or(|n| mate_in(result.score).is_some_and(|m| m > 0 && m <= n as i32));
+        let solved = played.is_some_and(|mv| {
+            (epd.best_moves.is_empty() || epd.best_moves.contains(&mv))
+                && !epd.avoid_moves.contains(&mv)
+        }) && mate_found;
+
+        let played_san = played.map_or("none".to_string(), |mv| epd.game.move_to_san(mv));
+        let expected: Vec<String> = epd
+            .best_moves
+            .iter()
+            .map(|mv| epd.game.move_to_san(*mv))
+            .collect();
+        if solved {
+            report.solved += 1;
+            println!("[{name}] \x1b[32mPASS\x1b[0m ({played_san}, {duration:.2?})");
+        } else {
+            report.failed.push(name.clone());
+            let mut wanted = Vec::new();
+            if !expected.is_empty() {
+                wanted.push(format!("bm {}", expected.join(" ")));
+            }
+            if !epd.avoid_moves.is_empty() {
+                let avoid: Vec<String> = epd
+                    .avoid_moves
+                    .iter()
+                    .map(|mv| epd.game.move_to_san(*mv))
+                    .collect();
+                wanted.push(format!("am {}", avoid.join(" ")));
+            }
+            if let Some(n) = epd.direct_mate {
+                wanted.push(format!("dm {n}"));
+            }
+            println!(
+                "[{name}] \x1b[31mFAIL\x1b[0m (Got: {played_san} score {}, Expected: {}, {duration:.2?})",
+                result.score,
+                wanted.join(", ")
+            );
+        }
+    }
+
+    let checked = report.solved + report.failed.len();
+    println!(
+        "\n--- Test Suite Finished in {:.2?} ---",
+        start_time.elapsed()
+    );
+    println!(
+        "Solved {}/{checked} positions, {} failed, {} skipped",
+        report.solved,
+        report.failed.len(),
+        report.skipped
+    );
+    report
+}
@@ -0,0 +1,129 @@
+use chess_bot::Piece;
+use chess_bot::game::epd::{Epd, EpdError};
+use chess_bot::game::san::SanError;
+use chess_bot::utils::test_runner::{SuiteLimits, run_epd_positions};
+
+#[test]
+fn parses_opcodes() {
+    let epd = Epd::parse(
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; c0 \"a; b\"; acd 12; ce +320;",
+    )
+    .unwrap();
+
+    assert_eq!(epd.best_moves, vec![(22, 46, None)]);
+    assert_eq!(epd.id.as_deref(), Some("WAC.001"));
+    assert_eq!(epd.comment.as_deref(), Some("a; b"));
+    assert_eq!(epd.analysis_depth, Some(12));
+    assert_eq!(epd.operation("ce"), Some(&["+320".to_string()][..]));
+    assert_eq!(epd.operations.len(), 5);
+}
+
+#[test]
+fn parses_several_moves_mates_and_counters() {
+    let epd = Epd::parse("4k3/P7/8/8/8/8/8/4K2R w K - am O-O a8=N; bm a8=Q a8=R; dm 2; hmvc 7; fmvn 40").unwrap();
+
+    assert_eq!(epd.avoid_moves, vec![(4, 6, None), (48, 56, Some(Piece::Knight))]);
+    assert_eq!(epd.best_moves, vec![(48, 56, Some(Piece::Queen)), (48, 56, Some(Piece::Rook))]);
+    assert_eq!(epd.direct_mate, Some(2));
+    assert_eq!(epd.game.to_fen(), "4k3/P7/8/8/8/8/8/4K2R w K - 7 40");
+}
+
+#[test]
+fn extra_whitespace_between_fields_is_ignored() {
+    let epd = Epd::parse("4k3/P7/8/8/8/8/8/4K2R   w\tK  -    bm a8=Q;  id \"spaced\";").unwrap();
+    assert_eq!(epd.game.to_fen(), "4k3/P7/8/8/8/8/8/4K2R w K - 0 1");
+    assert_eq!(epd.best_moves, vec![(48, 56, Some(Piece::Queen))]);
+    assert_eq!(epd.id.as_deref(), Some("spaced"));
+}
+
+#[test]
+fn reports_malformed_records() {
+    assert!(matches!(Epd::parse("8/8/8 w - - bm e4;"), Err(EpdError::InvalidPosition(_))));
+    assert_eq!(
+        Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"open").err(),
+        Some(EpdError::UnterminatedString)
+    );
+    assert_eq!(
+        Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - acd -1;").err(),
+        Some(EpdError::BadOperand {
+            opcode: "acd".to_string(),
+            operand: "-1".to_string(),
+        })
+    );
+    assert_eq!(
+        Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - bm Qd1;").err(),
+        Some(EpdError::BadMove {
+            opcode: "bm".to_string(),
+            error: SanError::IllegalMove("Qd1".to_string()),
+        })
+    );
+}
+
+#[test]
+fn suite_counts_solved_failed_and_skipped_positions() {
+    let suite = Epd::parse_all(
+        "# back rank mates\n\
+         6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8; id \"mate\";\n\
+         6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - dm 1; id \"direct mate\";\n\
+         6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - am Rd8; id \"avoid mate\";\n\
+         \n\
+         6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - id \"no answer\";\n",
+    )
+    .unwrap();
+    assert_eq!(suite.len(), 4);
+
+    let limits = SuiteLimits {
+        depth: Some(3),
+        ..SuiteLimits::default()
+    };
+    let report = run_epd_positions(&suite, limits);
+    assert_eq!(report.solved, 2);
+    assert_eq!(report.failed, vec!["avoid mate".to_string()]);
+    assert_eq!(report.skipped, 1);
+}
+
+#[test]
+fn suite_respects_node_and_time_limits() {
+    let suite = Epd::parse_all("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5 Bc4 Nc3 d4;").unwrap();
+
+    let by_nodes = SuiteLimits {
+        nodes: Some(20_000),
+        ..SuiteLimits::default()
+    };
+    // Without a depth the search would never finish unless the node limit stops it
+    let report = run_epd_positions(&suite, by_nodes);
+    assert_eq!(report.solved + report.failed.len(), 1);
+
+    let by_time = SuiteLimits {
+        movetime: Some(200),
+        ..SuiteLimits::default()
+    };
+    let start = std::time::Instant::now();
+    run_epd_positions(&suite, by_time);
+    assert!(start.elapsed().as_millis() < 2_000);
+}
+
+#[test]
+fn suite_without_limits_uses_a_bounded_depth() {
+    // No limits and no acd, the search must still end.
+    let suite = Epd::parse_all("8/8/8/4k3/8/8/4P3/4K3 w - - am Kd1; id \"pawn ending\";").unwrap();
+    let report = run_epd_positions(&suite, SuiteLimits::default());
+    assert_eq!(report.solved, 1);
+}
+
+#[test]
+fn suite_searches_with_its_own_table() {
+    let suite = Epd::parse_all(
+        "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8; id \"mate\";\n\
+         8/8/8/4k3/8/8/4P3/4K3 w - - am Kd1; id \"pawn ending\";\n",
+    )
+    .unwrap();
+    let limits = SuiteLimits {
+        depth: Some(4),
+        ..SuiteLimits::default()
+    };
+    assert_eq!(run_epd_positions(&suite, limits).solved, 2);
+    for epd in &suite {
+        assert!(epd.game.tt.probe(epd.game.zobrist_hash).is_none());
+    }
+}
@@ -0,0 +1,146 @@
+use chess_bot::Game;
+use chess_bot::game::fen::{FenError, FenMode};
+
+const PERFT_SUITE: &str = include_str!("../src/utils/perftsuite.txt");
+
+/// Asserts that writing `game` out and reading it back gives the same position.
+fn assert_round_trip(game: &Game) {
+    let fen = game.to_fen();
+    let parsed = Game::from_fen(&fen).unwrap_or_else(|e| panic!("{fen} does not parse: {e}"));
+    assert_eq!(parsed.to_fen(), fen);
+    assert_eq!(parsed.zobrist_hash, game.zobrist_hash, "hash differs for {fen}");
+}
+
+/// Round-trips every position reached from `game` within `depth` plies.
+fn round_trip_perft(game: &mut Game, depth: u32) {
+    assert_round_trip(game);
+    if depth == 0 {
+        return;
+    }
+    for &(from, to, promo) in game.generate_legal_moves().iter() {
+        let undo = game.make_move_unchecked(from, to, promo);
+        round_trip_perft(game, depth - 1);
+        game.unmake_move(undo);
+    }
+}
+
+#[test]
+fn start_position_to_fen() {
+    assert_eq!(
+        Game::new().to_fen(),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+    );
+}
+
+#[test]
+fn display_matches_to_fen() {
+    let game = Game::new();
+    assert_eq!(format!("{}", game.fen()), game.to_fen());
+}
+
+#[test]
+fn perft_suite_round_trips() {
+    for line in PERFT_SUITE.lines().filter(|l| !l.trim().is_empty()) {
+        let fen = line.split(';').next().unwrap().trim();
+        let game = Game::from_fen(fen).expect("valid FEN in the perft suite");
+        assert_eq!(game.to_fen(), fen);
+        assert_round_trip(&game);
+    }
+}
+
+#[test]
+fn perft_positions_round_trip() {
+    for line in PERFT_SUITE.lines().filter(|l| !l.trim().is_empty()) {
+        let fen = line.split(';').next().unwrap().trim();
+        let mut game = Game::from_fen(fen).expect("valid FEN in the perft suite");
+        round_trip_perft(&mut game, 3);
+    }
+}
+
+#[test]
+fn perft_suite_parses_strictly() {
+    for line in PERFT_SUITE.lines().filter(|l| !l.trim().is_empty()) {
+        let fen = line.split(';').next().unwrap().trim();
+        if let Err(e) = Game::from_fen_with_mode(fen, FenMode::Strict) {
+            panic!("{fen}: {e}");
+        }
+    }
+}
+
+#[test]
+fn malformed_fens_are_rejected() {
+    let cases = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+            FenError::MissingField("side to move"),
+        ),
+        (
+            "rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            FenError::InvalidPiece { character: 'x', index: 13 },
+        ),
+        (
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            FenError::InvalidPiece { character: '9', index: 18 },
+        ),
+        (
+            "rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            FenError::BadRankLength { rank: 7 },
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            FenError::WrongRankCount(7),
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+            FenError::InvalidSideToMove("x".to_string()),
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KX - 0 1",
+            FenError::InvalidCastling("KX".to_string()),
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1",
+            FenError::InvalidEnPassant("e9".to_string()),
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQQBNR w KQkq - 0 1",
+            FenError::KingCount { white: 0, black: 1 },
+        ),
+        (
+            "rnbqkbnP/pppppppp/8/8/8/8/PPPPPPP1/RNBQKBNR w KQq - 0 1",
+            FenError::PawnOnBackRank { square: 63 },
+        ),
+        ("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1", FenError::OpponentInCheck),
+    ];
+    for (fen, expected) in cases {
+        match Game::from_fen(fen) {
+            Ok(_) => panic!("{fen} was accepted"),
+            Err(e) => assert_eq!(e, expected, "{fen}"),
+        }
+    }
+}
+
+#[test]
+fn strict_mode_rejects_what_lenient_mode_repairs() {
+    let fen = "4k3/8/8/8/8/8/8/4K3 w KQkq e6 7";
+    assert_eq!(
+        Game::from_fen_with_mode(fen, FenMode::Strict).err(),
+        Some(FenError::MissingField("fullmove number"))
+    );
+    let game = Game::from_fen_with_mode(fen, FenMode::Lenient).expect("repairable FEN");
+    assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/4K3 w - - 7 1");
+
+    let no_rooks = "4k3/8/8/8/8/8/8/4K3 w K - 0 1";
+    assert_eq!(
+        Game::from_fen_with_mode(no_rooks, FenMode::Strict).err(),
+        Some(FenError::CastlingWithoutPieces('K'))
+    );
+
+    let no_pawn = "4k3/8/8/8/8/8/8/4K3 w - e6 0 1";
+    assert_eq!(
+        Game::from_fen_with_mode(no_pawn, FenMode::Strict).err(),
+        Some(FenError::ImpossibleEnPassant { square: 44 })
+    );
+    let pushed = "4k3/8/8/4p3/8/8/8/4K3 w - e6 0 1";
+    assert!(Game::from_fen_with_mode(pushed, FenMode::Strict).is_ok());
+}
@@ -0,0 +1,139 @@
+use chess_bot::Game;
+use chess_bot::game::GameState;
+use chess_bot::game::fen::FenMode;
+
+/// Plays `moves` in UCI notation, panicking on anything illegal.
+fn play(game: &mut Game, moves: &[&str]) {
+    for move_str in moves {
+        let mv = Game::parse_move(move_str).expect("well-formed move");
+        assert!(
+            game.generate_legal_moves().iter().any(|m| *m == mv),
+            "illegal move {move_str}"
+        );
+        game.make_move_unchecked(mv.0, mv.1, mv.2);
+    }
+}
+
+#[test]
+fn knight_shuffle_repeats_the_start_position() {
+    let mut game = Game::new();
+    play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+    assert_eq!(game.repetition_count(), 1);
+
+    play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+    assert_eq!(game.repetition_count(), 2);
+}
+
+#[test]
+fn pawn_move_ends_repetition_window() {
+    let mut game = Game::new();
+    play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "e2e4"]);
+    assert_eq!(game.halfmove_clock, 0);
+    assert_eq!(game.repetition_count(), 0);
+}
+
+#[test]
+fn pawn_move_resets_halfmove_clock() {
+    let mut game = Game::new();
+    play(&mut game, &["g1f3", "g8f6", "f3g1"]);
+    assert_eq!(game.halfmove_clock, 3);
+
+    play(&mut game, &["e7e5"]);
+    assert_eq!(game.halfmove_clock, 0);
+}
+
+#[test]
+fn unmake_restores_history_and_clock() {
+    let mut game = Game::new();
+    play(&mut game, &["g1f3"]);
+    let hash = game.zobrist_hash;
+
+    let undo = game.make_move_unchecked(57, 42, None); // b8c6
+    assert_eq!(game.halfmove_clock, 2);
+    assert_eq!(game.position_history.len(), 2);
+
+    game.unmake_move(undo);
+    assert_eq!(game.halfmove_clock, 1);
+    assert_eq!(game.position_history.len(), 1);
+    assert_eq!(game.zobrist_hash, hash);
+
+    let undo = game.make_null_move();
+    assert_eq!((game.halfmove_clock, game.plies_since_null), (1, 0));
+    game.unmake_null_move(undo);
+    assert_eq!((game.halfmove_clock, game.plies_since_null), (1, 1));
+    assert_eq!(game.position_history.len(), 1);
+}
+
+#[test]
+fn repetitions_do_not_reach_across_a_null_move() {
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").expect("valid FEN");
+    play(&mut game, &["a1b1", "e8e7"]);
+    game.make_null_move();
+    // Black loses a tempo, so white is to move in the same position as before the null move.
+    play(&mut game, &["e7d7", "b1b2", "d7d8", "b2b1", "d8e7"]);
+    assert_eq!(game.repetition_count(), 0);
+    assert_eq!(game.halfmove_clock, 7);
+
+    play(&mut game, &["b1b2", "e7e8", "b2b1", "e8e7"]);
+    assert_eq!(game.repetition_count(), 1);
+    assert_eq!(game.halfmove_clock, 11);
+}
+
+#[test]
+fn fifty_move_rule_after_hundred_quiet_plies() {
+    let mut game = Game::new();
+    for _ in 0..25 {
+        play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+    }
+    assert_eq!(game.halfmove_clock, 100);
+    assert!(game.is_fifty_move_draw());
+}
+
+#[test]
+fn capturing_a_rook_in_its_corner_removes_that_castling_right() {
+    let mut game = Game::from_fen("r3k2r/8/8/8/8/8/6b1/R3K2R b KQkq - 0 1").expect("valid FEN");
+    play(&mut game, &["g2h1"]);
+    assert_eq!(game.castling & 0x0F, 0b0111);
+
+    let moves = game.generate_legal_moves();
+    assert!(!moves.iter().any(|m| *m == (4, 6, None)), "e1g1 without the h1 rook");
+    assert!(moves.iter().any(|m| *m == (4, 2, None)));
+}
+
+#[test]
+fn fen_move_counters_are_parsed() {
+    let game = Game::from_fen("8/8/4k3/8/8/4K3/8/7R w - - 37 52").expect("valid FEN");
+    assert_eq!(game.halfmove_clock, 37);
+    assert_eq!(game.fullmove_number, 52);
+
+    // Both counters may be left out
+    let game = Game::from_fen("8/8/4k3/8/8/4K3/8/7R w - -").expect("valid FEN");
+    assert_eq!(game.halfmove_clock, 0);
+    assert_eq!(game.fullmove_number, 1);
+
+    let bad_clock = "8/8/4k3/8/8/4K3/8/7R w - - x 1";
+    assert!(Game::from_fen_with_mode(bad_clock, FenMode::Strict).is_err());
+    assert_eq!(Game::from_fen(bad_clock).expect("lenient parse").halfmove_clock, 0);
+}
+
+#[test]
+fn fullmove_number_advances_after_black_moves() {
+    let mut game = Game::new();
+    play(&mut game, &["e2e4"]);
+    assert_eq!(game.fullmove_number, 1);
+
+    let undo = game.make_move_unchecked(52, 36, None); // e7e5
+    assert_eq!(game.fullmove_number, 2);
+
+    game.unmake_move(undo);
+    assert_eq!(game.fullmove_number, 1);
+}
+
+#[test]
+fn fifty_move_rule_is_adjudicated() {
+    let mut game = Game::from_fen("8/8/4k3/8/8/4K3/8/7R w - - 99 80").expect("valid FEN");
+    assert_eq!(game.game_state(), GameState::Normal);
+
+    play(&mut game, &["h1h2"]);
+    assert_eq!(game.game_state(), GameState::FiftyMoves);
+}
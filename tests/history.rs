@@ -0,0 +1,78 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+use chess_bot::search::history::{HISTORY_MAX, History, PieceTo, history_bonus};
+use chess_bot::search::{SearchLimits, find_best_move};
+use chess_bot::{Game, Piece};
+
+const KNIGHT_F3: PieceTo = PieceTo { piece: Piece::Knight as usize, to: 21 };
+const PAWN_E4: PieceTo = PieceTo { piece: Piece::Pawn as usize, to: 28 };
+
+#[test]
+fn gravity_keeps_scores_bounded() {
+    let mut history = History::new();
+    for _ in 0..1000 {
+        history.update_quiet(KNIGHT_F3, [None, None], history_bonus(20));
+        history.update_quiet(PAWN_E4, [None, None], -history_bonus(20));
+    }
+    let best = history.quiet_score(KNIGHT_F3, [None, None]);
+    let worst = history.quiet_score(PAWN_E4, [None, None]);
+    assert!((HISTORY_MAX * 9 / 10..=HISTORY_MAX).contains(&best), "{best}");
+    assert!((-HISTORY_MAX..=-HISTORY_MAX * 9 / 10).contains(&worst), "{worst}");
+}
+
+#[test]
+fn continuation_history_depends_on_the_previous_moves() {
+    let mut history = History::new();
+    history.update_quiet(KNIGHT_F3, [Some(PAWN_E4), None], history_bonus(4));
+
+    let plain = history.quiet_score(KNIGHT_F3, [None, None]);
+    assert!(plain > 0);
+    assert!(history.quiet_score(KNIGHT_F3, [Some(PAWN_E4), None]) > plain);
+    assert_eq!(history.quiet_score(KNIGHT_F3, [None, Some(PAWN_E4)]), plain);
+}
+
+#[test]
+fn aging_halves_scores_and_keeps_countermoves() {
+    let mut history = History::new();
+    history.update_capture(KNIGHT_F3, Piece::Queen, 1000);
+    history.set_countermove(PAWN_E4, (6, 21, None));
+    history.age();
+
+    assert_eq!(history.capture_score(KNIGHT_F3, Piece::Queen), 500);
+    assert_eq!(history.countermove(PAWN_E4), Some((6, 21, None)));
+}
+
+#[test]
+fn searches_share_history_through_the_limits() {
+    let shared = Arc::new(Mutex::new(History::new()));
+    let limits = SearchLimits {
+        history: Some(Arc::clone(&shared)),
+        ..SearchLimits::depth(5)
+    };
+    find_best_move(&mut Game::new(), &limits, &Arc::new(AtomicBool::new(false)));
+
+    let history = shared.lock().unwrap();
+    let learned = (0..12)
+        .flat_map(|piece| (0..64).map(move |to| PieceTo { piece, to }))
+        .any(|moved| history.quiet_score(moved, [None, None]) != 0);
+    assert!(learned);
+}
+
+#[test]
+fn en_passant_counts_as_a_capture() {
+    // White's pawn only reaches d6 by taking, en passant after d5 or plainly after d6,
+    // so it must never get a quiet history score.
+    let shared = Arc::new(Mutex::new(History::new()));
+    let limits = SearchLimits {
+        history: Some(Arc::clone(&shared)),
+        ..SearchLimits::depth(6)
+    };
+    let mut game = Game::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1").unwrap();
+    find_best_move(&mut game, &limits, &Arc::new(AtomicBool::new(false)));
+
+    let history = shared.lock().unwrap();
+    let pawn_d6 = PieceTo::new(Piece::Pawn, true, 43);
+    assert_eq!(history.quiet_score(pawn_d6, [None, None]), 0);
+    assert_ne!(history.capture_score(pawn_d6, Piece::Pawn), 0);
+}
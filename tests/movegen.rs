@@ -0,0 +1,60 @@
+use chess_bot::Game;
+use chess_bot::board::movegen::generate_pseudo_legal_moves;
+
+const POSITIONS: [&str; 4] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+];
+
+/// Whether the side that just moved left its own king in check.
+fn mover_in_check(game: &Game) -> bool {
+    let mut game = game.clone();
+    game.is_white_turn = !game.is_white_turn;
+    game.is_in_check()
+}
+
+/// Checks that the pseudo-legal moves which keep the king safe are exactly the legal
+/// moves, at every node of the perft tree.
+fn check_pseudo_legal(game: &mut Game, depth: u32) {
+    let legal = game.generate_legal_moves();
+    for &(from, to, promo) in generate_pseudo_legal_moves(game).iter() {
+        let mut after = game.clone();
+        after.make_move_unchecked(from, to, promo);
+        let is_legal = legal.iter().any(|m| *m == (from, to, promo));
+        assert_eq!(!mover_in_check(&after), is_legal, "{} {from} {to} {promo:?}", game.to_fen());
+    }
+    if depth == 0 {
+        return;
+    }
+    for &(from, to, promo) in legal.iter() {
+        let undo = game.make_move_unchecked(from, to, promo);
+        check_pseudo_legal(game, depth - 1);
+        game.unmake_move(undo);
+    }
+}
+
+#[test]
+fn pseudo_legal_moves_match_legal_moves() {
+    for fen in POSITIONS {
+        check_pseudo_legal(&mut Game::from_fen(fen).unwrap(), 2);
+    }
+}
+
+#[test]
+fn double_pushes_do_not_jump_over_pieces() {
+    let game = Game::from_fen("4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1").unwrap();
+    assert!(
+        !generate_pseudo_legal_moves(&game)
+            .iter()
+            .any(|m| *m == (12, 28, None))
+    );
+
+    let game = Game::from_fen("4k3/4p3/4N3/8/8/8/8/4K3 b - - 0 1").unwrap();
+    assert!(
+        !generate_pseudo_legal_moves(&game)
+            .iter()
+            .any(|m| *m == (52, 36, None))
+    );
+}
@@ -0,0 +1,148 @@
+use chess_bot::board::movegen::{
+    generate_pseudo_legal_captures, generate_pseudo_legal_moves, generate_pseudo_legal_quiets,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use chess_bot::search::Search;
+use chess_bot::search::movepick::MovePicker;
+use chess_bot::{Game, Piece};
+
+type Move = (usize, usize, Option<Piece>);
+
+const POSITIONS: [&str; 5] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+];
+
+fn sorted(mut moves: Vec<Move>) -> Vec<Move> {
+    moves.sort_by_key(|&(from, to, promo)| (from, to, promo.map(|p| p as usize)));
+    moves
+}
+
+fn picked(game: &Game, tt_move: Option<Move>, search: &mut Search) -> Vec<Move> {
+    let mut picker = MovePicker::new(game, tt_move, 0, search);
+    let mut moves = Vec::new();
+    while let Some(m) = picker.next(game, 0, search) {
+        moves.push(m);
+    }
+    moves
+}
+
+#[test]
+fn captures_and_quiets_split_all_moves() {
+    for fen in POSITIONS {
+        let game = Game::from_fen(fen).unwrap();
+        let mut split: Vec<Move> = generate_pseudo_legal_captures(&game).iter().copied().collect();
+        split.extend(generate_pseudo_legal_quiets(&game).iter().copied());
+        let all: Vec<Move> = generate_pseudo_legal_moves(&game).iter().copied().collect();
+        assert_eq!(sorted(split), sorted(all), "{fen}");
+    }
+}
+
+#[test]
+fn picker_yields_every_move_once() {
+    for fen in POSITIONS {
+        let game = Game::from_fen(fen).unwrap();
+        let all: Vec<Move> = generate_pseudo_legal_moves(&game).iter().copied().collect();
+        let mut search = Search::new();
+        assert_eq!(sorted(picked(&game, None, &mut search)), sorted(all.clone()), "{fen}");
+
+        // The TT move comes first and is not repeated by a later stage.
+        let tt_move = *all.last().unwrap();
+        let moves = picked(&game, Some(tt_move), &mut search);
+        assert_eq!(moves[0], tt_move);
+        assert_eq!(sorted(moves), sorted(all), "{fen}");
+    }
+}
+
+#[test]
+fn picker_ignores_a_tt_move_from_another_position() {
+    let game = Game::new();
+    let mut search = Search::new();
+    // e7e5 belongs to the other side, a TT collision must not hand it out.
+    let moves = picked(&game, Some((52, 36, None)), &mut search);
+    assert_eq!(moves.len(), 20);
+    assert!(!moves.contains(&(52, 36, None)));
+}
+
+#[test]
+fn good_captures_come_first_and_losing_captures_last() {
+    // exd5 at worst trades pawns, Qxb7 loses the queen to the bishop on a8.
+    let game = Game::from_fen("b3k3/1p6/2p5/3p4/4P3/8/8/1Q2K3 w - - 0 1").unwrap();
+    let mut search = Search::new();
+    let moves = picked(&game, None, &mut search);
+
+    assert_eq!(moves[0], (28, 35, None));
+    assert_eq!(moves.last(), Some(&(1, 49, None)));
+}
+
+/// Plays random legal moves from each test position, collecting every position on the way.
+fn random_positions(rng: &mut StdRng) -> Vec<Game> {
+    let mut games = Vec::new();
+    for fen in POSITIONS {
+        let mut game = Game::from_fen(fen).unwrap();
+        for _ in 0..40 {
+            let moves: Vec<Move> = game.generate_legal_moves().iter().copied().collect();
+            if moves.is_empty() {
+                break;
+            }
+            let (from, to, promo) = moves[rng.random_range(0..moves.len())];
+            game.make_move_unchecked(from, to, promo);
+            games.push(game.clone());
+        }
+    }
+    games
+}
+
+#[test]
+fn pseudo_legality_matches_generation_for_random_moves() {
+    let mut rng = StdRng::seed_from_u64(0x5EED);
+    let promotions = [None, Some(Piece::Queen), Some(Piece::Knight), Some(Piece::King), Some(Piece::Pawn)];
+    for game in random_positions(&mut rng) {
+        let generated = generate_pseudo_legal_moves(&game);
+        for _ in 0..500 {
+            let mv = (
+                rng.random_range(0..64),
+                rng.random_range(0..64),
+                promotions[rng.random_range(0..promotions.len())],
+            );
+            let expected = generated.iter().any(|m| *m == mv);
+            assert_eq!(game.is_pseudo_legal(mv), expected, "{} {mv:?}", game.to_fen());
+        }
+        for m in generated.iter() {
+            assert!(game.is_pseudo_legal(*m), "{} {m:?}", game.to_fen());
+        }
+    }
+}
+
+#[test]
+fn moves_from_other_positions_are_rejected_unless_pseudo_legal() {
+    // Mimics hash collisions: every move of one position is tried in the next one.
+    let mut rng = StdRng::seed_from_u64(7);
+    let games = random_positions(&mut rng);
+    for pair in games.windows(2) {
+        let generated = generate_pseudo_legal_moves(&pair[1]);
+        for m in generate_pseudo_legal_moves(&pair[0]).iter() {
+            let expected = generated.iter().any(|g| g == m);
+            assert_eq!(pair[1].is_pseudo_legal(*m), expected, "{} {m:?}", pair[1].to_fen());
+        }
+    }
+}
+
+#[test]
+fn castling_and_en_passant_are_validated() {
+    let game = Game::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+    assert!(game.is_pseudo_legal((4, 6, None)));
+    assert!(game.is_pseudo_legal((4, 2, None)));
+    assert!(game.is_pseudo_legal((36, 43, None)));
+
+    // Without the rights, or with the path blocked, the king cannot go two squares.
+    let game = Game::from_fen("r3k2r/8/8/3pP3/8/8/8/RN2K1NR w - - 0 1").unwrap();
+    assert!(!game.is_pseudo_legal((4, 6, None)));
+    assert!(!game.is_pseudo_legal((4, 2, None)));
+    assert!(!game.is_pseudo_legal((36, 43, None)));
+}
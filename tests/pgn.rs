@@ -0,0 +1,134 @@
+use chess_bot::Game;
+use chess_bot::game::pgn::{PgnError, PgnGame, parse_pgn};
+use chess_bot::game::san::SanError;
+
+const OPERA_GAME: &str = r#"[Event "Paris"]
+[Site "Paris FRA"]
+[Date "1858.??.??"]
+[Round "?"]
+[White "Paul Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 d6 3. d4 Bg4 {This is a weak move already.} 4. dxe5 Bxf3 5. Qxf3
+dxe5 6. Bc4 Nf6 7. Qb3 Qe7 8. Nc3 c6 9. Bg5 b5?! 10. Nxb5! cxb5 11. Bxb5+ Nbd7
+12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6 15. Bxd7+ Nxd7 16. Qb8+ Nxb8 17. Rd8# 1-0
+"#;
+
+#[test]
+fn reads_tags_moves_comments_and_nags() {
+    let games = parse_pgn(OPERA_GAME).unwrap();
+    assert_eq!(games.len(), 1);
+    let game = &games[0];
+
+    assert_eq!(game.tag("White"), Some("Paul Morphy"));
+    assert_eq!(game.result, "1-0");
+    assert_eq!(game.moves.len(), 33);
+    assert_eq!(game.moves[5].comment.as_deref(), Some("This is a weak move already."));
+    assert_eq!(game.moves[17].nags, vec![6]);
+    assert_eq!(game.moves[18].nags, vec![1]);
+
+    let positions = game.positions();
+    assert_eq!(positions.len(), 34);
+    let last = positions.last().unwrap();
+    assert!(last.is_in_check());
+    assert!(last.generate_legal_moves().is_empty());
+    assert_eq!(last.to_fen(), game.final_position().to_fen());
+}
+
+#[test]
+fn reads_variations_from_the_position_before_the_move() {
+    let pgn = "1. e4 (1. d4 d5 (1... Nf6 2. c4) 2. c4) 1... c5 {Sicilian} (1... e5 $1 2. Nf3) 2. Nf3 *";
+    let game = &parse_pgn(pgn).unwrap()[0];
+
+    assert_eq!(game.result, "*");
+    assert_eq!(game.moves.len(), 3);
+    let d4 = &game.moves[0].variations[0];
+    assert_eq!(d4.len(), 3);
+    assert_eq!(d4[1].variations[0].len(), 2);
+    assert_eq!(game.moves[1].comment.as_deref(), Some("Sicilian"));
+    assert_eq!(game.moves[1].variations[0][0].nags, vec![1]);
+}
+
+#[test]
+fn reads_several_games_and_set_up_positions() {
+    let pgn = format!(
+        "{OPERA_GAME}\n[Event \"Ending\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n1. e4 Kd7 2. e5 1/2-1/2\n"
+    );
+    let games = parse_pgn(&pgn).unwrap();
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[1].result, "1/2-1/2");
+    assert_eq!(games[1].final_position().to_fen(), "8/3k4/8/4P3/8/8/8/4K3 b - - 0 2");
+}
+
+#[test]
+fn written_games_read_back_the_same() {
+    let pgn = "[Event \"Test \\\"quoted\\\"\"]\n\n{Opening} 1. e4 (1. d4 d5) 1... e5 $2 {A long comment that \
+               should make the line wrap somewhere} 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 \
+               7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7 *";
+    let game = &parse_pgn(pgn).unwrap()[0];
+    let written = game.to_string();
+
+    assert!(written.lines().all(|line| line.len() <= 80), "{written}");
+    assert!(written.contains("[Event \"Test \\\"quoted\\\"\"]"));
+    assert!(written.contains("{Opening} 1. e4 (1. d4 d5) 1... e5 $2"), "{written}");
+
+    let reread = &parse_pgn(&written).unwrap()[0];
+    assert_eq!(reread.tags, game.tags);
+    assert_eq!(reread.moves, game.moves);
+    assert_eq!(reread.result, game.result);
+    assert_eq!(reread.to_string(), written);
+}
+
+#[test]
+fn comments_closing_a_variation_are_kept() {
+    let pgn = "1. e4 (1. d4 d5 {end of the line}) ({no moves here}) 1... e5 *";
+    let game = &parse_pgn(pgn).unwrap()[0];
+    assert_eq!(game.moves[0].variations[0][1].comment.as_deref(), Some("end of the line"));
+    assert_eq!(game.moves[0].comment.as_deref(), Some("no moves here"));
+
+    let written = game.to_string();
+    assert!(written.contains("{end of the line})"), "{written}");
+    assert!(written.contains("{no moves here}"), "{written}");
+    let reread = &parse_pgn(&written).unwrap()[0];
+    assert_eq!(reread.moves, game.moves);
+    assert_eq!(reread.to_string(), written);
+}
+
+#[test]
+fn builds_games_move_by_move() {
+    let start = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+    let mut game = PgnGame::new(&start);
+    assert!(game.push((60, 59, None)));
+    assert!(game.push((12, 28, None)));
+    assert!(!game.push((12, 28, None)));
+    game.moves[1].comment = Some("+0.85/12".to_string());
+    game.set_result("1-0");
+
+    let written = game.to_string();
+    assert!(written.contains("[Result \"1-0\"]"));
+    assert!(written.contains("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]"));
+    assert!(written.ends_with("1... Kd8 2. e4 {+0.85/12} 1-0\n"), "{written}");
+
+    let reread = &parse_pgn(&written).unwrap()[0];
+    assert_eq!(reread.moves, game.moves);
+    assert_eq!(reread.start.to_fen(), start.to_fen());
+}
+
+#[test]
+fn reports_malformed_pgn() {
+    assert_eq!(
+        parse_pgn("1. e4 e5 2. Ke3 *").err(),
+        Some(PgnError::BadMove {
+            move_number: 2,
+            san: "Ke3".to_string(),
+            error: SanError::IllegalMove("Ke3".to_string()),
+        })
+    );
+    assert_eq!(parse_pgn("1. e4 {never closed").err(), Some(PgnError::UnterminatedComment));
+    assert_eq!(parse_pgn("1. e4 (1. d4 *").err(), Some(PgnError::UnterminatedVariation));
+    assert_eq!(parse_pgn("1. e4 ) *").err(), Some(PgnError::UnexpectedToken(")".to_string())));
+    assert_eq!(parse_pgn("( 1. e4 ) *").err(), Some(PgnError::UnexpectedToken("(".to_string())));
+    assert_eq!(parse_pgn("[Event]\n1. e4 *").err(), Some(PgnError::BadTag("[Event]".to_string())));
+    assert!(matches!(parse_pgn("[FEN \"8/8 w - - 0 1\"]\n*"), Err(PgnError::InvalidFen(_))));
+}
@@ -0,0 +1,70 @@
+use chess_bot::Game;
+use chess_bot::search::eval::{eval, quiescence_search};
+use chess_bot::search::tt::Flag;
+use chess_bot::search::{MATE_SCORE, Search, mate_in};
+
+const INFINITY: i32 = MATE_SCORE + 1;
+
+fn qsearch(fen: &str, checks: bool) -> i32 {
+    qsearch_window(fen, -INFINITY, INFINITY, checks)
+}
+
+fn qsearch_window(fen: &str, alpha: i32, beta: i32, checks: bool) -> i32 {
+    let mut game = Game::from_fen(fen).expect("valid FEN");
+    quiescence_search(&mut game, 0, alpha, beta, checks, &mut Search::new())
+}
+
+#[test]
+fn checkmate_is_recognised_in_check() {
+    // Back-rank mate, the side to move may not stand pat and has no evasion.
+    assert_eq!(qsearch("R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1", false), -MATE_SCORE);
+}
+
+#[test]
+fn evasions_are_searched_instead_of_standing_pat() {
+    // Up a queen, but in check from a knight forking king and queen.
+    let fen = "4k3/8/8/8/8/8/2n5/Q3K3 w - - 0 1";
+    let game = Game::from_fen(fen).unwrap();
+    assert!(game.is_in_check());
+    let score = qsearch(fen, false);
+    assert!(score < eval(&game), "{score}");
+}
+
+#[test]
+fn quiet_checks_are_only_tried_when_asked_for() {
+    let fen = "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1";
+    assert_eq!(mate_in(qsearch(fen, true)), Some(1));
+    assert_eq!(mate_in(qsearch(fen, false)), None);
+}
+
+#[test]
+fn hopeless_captures_that_give_check_are_tried_at_the_first_ply() {
+    // Down a piece, Bxf7 only wins a pawn, which delta pruning gives up on. But it is mate.
+    let fen = "r2qkb1r/pp2np1p/3p1p2/2p1N1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 0 2";
+    let alpha = -100;
+    assert!(eval(&Game::from_fen(fen).unwrap()) + 300 < alpha);
+    assert_eq!(mate_in(qsearch_window(fen, alpha, INFINITY, true)), Some(1));
+    assert_eq!(qsearch_window(fen, alpha, INFINITY, false), alpha);
+}
+
+#[test]
+fn results_are_stored_with_bounds() {
+    let mut game = Game::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+    let mut search = Search::new();
+
+    let beta = -INFINITY + 1;
+    quiescence_search(&mut game, 0, -INFINITY, beta, false, &mut search);
+    let entry = game.tt.probe(game.zobrist_hash).expect("stored");
+    assert_eq!(entry.depth, 0);
+    assert_eq!(entry.flag, Flag::LowerBound);
+
+    // A second search with the same window is answered by the table.
+    let nodes = search.nodes_searched;
+    assert_eq!(quiescence_search(&mut game, 0, -INFINITY, beta, false, &mut search), beta);
+    assert_eq!(search.nodes_searched, nodes + 1);
+
+    let mut game = Game::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+    quiescence_search(&mut game, 0, -INFINITY, INFINITY, false, &mut search);
+    let entry = game.tt.probe(game.zobrist_hash).expect("stored");
+    assert_eq!(entry.flag, Flag::Exact);
+}
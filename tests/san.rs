@@ -0,0 +1,124 @@
+use chess_bot::game::san::SanError;
+use chess_bot::{Game, Piece};
+
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+fn game(fen: &str) -> Game {
+    Game::from_fen(fen).unwrap()
+}
+
+/// Writes every legal move from `game` within `depth` plies and checks it reads back as the same move.
+fn round_trip_perft(game: &Game, depth: u32) {
+    if depth == 0 {
+        return;
+    }
+    for &mv in game.generate_legal_moves().iter() {
+        let san = game.move_to_san(mv);
+        assert_eq!(game.parse_san(&san), Ok(mv), "{san} in {}", game.to_fen());
+
+        let mut next = game.clone();
+        next.make_move_unchecked(mv.0, mv.1, mv.2);
+        round_trip_perft(&next, depth - 1);
+    }
+}
+
+#[test]
+fn san_round_trips_from_startpos_and_kiwipete() {
+    round_trip_perft(&Game::new(), 3);
+    round_trip_perft(&game(KIWIPETE), 2);
+}
+
+#[test]
+fn writes_pawn_piece_and_castling_moves() {
+    let start = Game::new();
+    assert_eq!(start.move_to_san((12, 28, None)), "e4");
+    assert_eq!(start.move_to_san((6, 21, None)), "Nf3");
+
+    let kiwipete = game(KIWIPETE);
+    assert_eq!(kiwipete.move_to_san((4, 6, None)), "O-O");
+    assert_eq!(kiwipete.move_to_san((4, 2, None)), "O-O-O");
+    assert_eq!(kiwipete.move_to_san((21, 23, None)), "Qxh3");
+    assert_eq!(kiwipete.move_to_san((14, 23, None)), "gxh3");
+}
+
+#[test]
+fn writes_en_passant_and_promotions() {
+    let ep = game("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+    assert_eq!(ep.move_to_san((36, 45, None)), "exf6");
+
+    let promo = game("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+    assert_eq!(promo.move_to_san((52, 60, Some(Piece::Queen))), "e8=Q");
+    assert_eq!(promo.move_to_san((52, 59, Some(Piece::Knight))), "exd8=N");
+}
+
+#[test]
+fn disambiguates_by_file_rank_and_square() {
+    // Knights on b1 and f1 both reach d2
+    let by_file = game("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1");
+    assert_eq!(by_file.move_to_san((1, 11, None)), "Nbd2");
+
+    // Rooks on a1 and a5 both reach a3
+    let by_rank = game("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1");
+    assert_eq!(by_rank.move_to_san((0, 16, None)), "R1a3");
+
+    // Queens on a1, a3 and c1 all reach b2, a1 shares a file and a rank
+    let by_square = game("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1");
+    assert_eq!(by_square.move_to_san((0, 9, None)), "Qa1b2");
+    assert_eq!(by_square.parse_san("Qa1b2"), Ok((0, 9, None)));
+}
+
+#[test]
+fn marks_check_and_mate() {
+    let fools_mate = game("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2");
+    assert_eq!(fools_mate.move_to_san((59, 31, None)), "Qh4#");
+
+    let check = game("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+    assert_eq!(check.move_to_san((0, 56, None)), "Ra8+");
+}
+
+#[test]
+fn parses_with_suffixes_and_alternative_spellings() {
+    let kiwipete = game(KIWIPETE);
+    assert_eq!(kiwipete.parse_san("0-0"), Ok((4, 6, None)));
+    assert_eq!(kiwipete.parse_san("O-O-O"), Ok((4, 2, None)));
+    assert_eq!(kiwipete.parse_san("Qxh3!?"), Ok((21, 23, None)));
+
+    let promo = game("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+    assert_eq!(promo.parse_san("e8Q"), Ok((52, 60, Some(Piece::Queen))));
+    assert_eq!(promo.parse_san("exd8=R+"), Ok((52, 59, Some(Piece::Rook))));
+}
+
+#[test]
+fn reports_ambiguous_illegal_and_malformed_moves() {
+    let by_file = game("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1");
+    assert_eq!(
+        by_file.parse_san("Nd2"),
+        Err(SanError::AmbiguousMove {
+            san: "Nd2".to_string(),
+            candidates: vec!["b1d2".to_string(), "f1d2".to_string()],
+        })
+    );
+
+    let start = Game::new();
+    assert_eq!(start.parse_san("e5"), Err(SanError::IllegalMove("e5".to_string())));
+    assert_eq!(start.parse_san("O-O"), Err(SanError::IllegalMove("O-O".to_string())));
+    assert_eq!(start.parse_san("Nf6"), Err(SanError::IllegalMove("Nf6".to_string())));
+    assert_eq!(start.parse_san("Zf3"), Err(SanError::Malformed("Zf3".to_string())));
+    assert_eq!(start.parse_san("e9"), Err(SanError::Malformed("e9".to_string())));
+    assert_eq!(start.parse_san(""), Err(SanError::Malformed("".to_string())));
+
+    // A promotion must name its piece
+    let promo = game("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+    assert_eq!(promo.parse_san("e8"), Err(SanError::IllegalMove("e8".to_string())));
+}
+
+#[test]
+fn pawn_captures_need_the_origin_file() {
+    let capture = game("4k3/8/8/3p4/2P5/8/8/4K3 w - - 0 1");
+    assert_eq!(capture.parse_san("cxd5"), Ok((26, 35, None)));
+    assert_eq!(capture.parse_san("d5"), Err(SanError::IllegalMove("d5".to_string())));
+    assert_eq!(capture.parse_san("xd5"), Err(SanError::IllegalMove("xd5".to_string())));
+
+    let promo = game("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+    assert_eq!(promo.parse_san("d8=Q"), Err(SanError::IllegalMove("d8=Q".to_string())));
+}
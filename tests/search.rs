@@ -0,0 +1,296 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chess_bot::Game;
+use chess_bot::search::time::{TimeControl, TimeManager};
+use chess_bot::search::tt::Flag;
+use chess_bot::search::{Pruning, SearchLimits, analyse, find_best_move, mate_in};
+use chess_bot::utils::bench::{BENCH_POSITIONS, DEFAULT_BENCH_DEPTH};
+
+/// Searches `fen` to `depth` and returns the reported mate distance, if any.
+fn reported_mate(fen: &str, depth: u8) -> Option<i32> {
+    let mut game = Game::from_fen(fen).expect("valid FEN");
+    let stop_signal = Arc::new(AtomicBool::new(false));
+    let result = find_best_move(&mut game, &SearchLimits::depth(depth), &stop_signal);
+    mate_in(result.score)
+}
+
+#[test]
+fn mate_in_one() {
+    assert_eq!(reported_mate("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 4), Some(1));
+}
+
+#[test]
+fn mate_in_two() {
+    let fen = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1";
+    assert_eq!(reported_mate(fen, 4), Some(2));
+}
+
+#[test]
+fn mate_in_two_with_rooks() {
+    assert_eq!(reported_mate("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", 6), Some(2));
+}
+
+#[test]
+fn mate_in_three_for_white() {
+    assert_eq!(reported_mate("8/k7/8/1K6/8/8/8/7Q w - - 0 1", 11), Some(3));
+}
+
+#[test]
+fn mate_in_three_for_black() {
+    assert_eq!(reported_mate("7q/8/8/8/1k6/8/K7/8 b - - 0 1", 11), Some(3));
+}
+
+#[test]
+fn deep_search_does_not_stop_at_the_first_mate_found() {
+    // A mate found at a shallow depth may not be the shortest one.
+    assert_eq!(reported_mate("7q/8/8/8/1k6/8/K7/8 b - - 0 1", 14), Some(3));
+}
+
+#[test]
+fn fifty_move_draw_comes_before_the_tt() {
+    let mut game = Game::from_fen("7k/8/8/8/8/8/8/KQ6 w - - 0 1").expect("valid FEN");
+    game.halfmove_clock = 99;
+    // Every reply position is stored as lost for black, but any quiet move ends in a draw.
+    for (from, to, promo) in game.generate_legal_moves().iter().copied().collect::<Vec<_>>() {
+        let undo = game.make_move_unchecked(from, to, promo);
+        game.tt.store(game.zobrist_hash, 50, -900, Flag::Exact, None, None);
+        game.unmake_move(undo);
+    }
+    let stop_signal = Arc::new(AtomicBool::new(false));
+    let result = find_best_move(&mut game, &SearchLimits::depth(3), &stop_signal);
+    assert_eq!(result.score, 0);
+}
+
+#[test]
+fn mate_on_the_hundredth_ply_still_counts() {
+    let mut game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").expect("valid FEN");
+    game.halfmove_clock = 99;
+    let stop_signal = Arc::new(AtomicBool::new(false));
+    let result = find_best_move(&mut game, &SearchLimits::depth(3), &stop_signal);
+    assert_eq!(mate_in(result.score), Some(1));
+}
+
+#[test]
+fn multi_pv_returns_distinct_ranked_lines() {
+    let mut game = Game::new();
+    let stop_signal = Arc::new(AtomicBool::new(false));
+    let limits = SearchLimits {
+        multi_pv: 3,
+        ..SearchLimits::depth(3)
+    };
+    let lines = analyse(&mut game, &limits, &stop_signal);
+
+    assert_eq!(lines.len(), 3);
+    assert!(lines.windows(2).all(|w| w[0].score >= w[1].score));
+    for (i, line) in lines.iter().enumerate() {
+        assert_eq!(line.pv.first().copied(), line.best_move);
+        assert!(lines[..i].iter().all(|other| other.best_move != line.best_move));
+    }
+}
+
+#[test]
+fn multi_pv_is_capped_by_legal_moves() {
+    let mut game = Game::from_fen("7k/8/8/8/8/8/8/K7 w - - 0 1").expect("valid FEN");
+    let stop_signal = Arc::new(AtomicBool::new(false));
+    let limits = SearchLimits {
+        multi_pv: 10,
+        ..SearchLimits::depth(2)
+    };
+    assert_eq!(analyse(&mut game, &limits, &stop_signal).len(), 3);
+}
+
+#[test]
+fn forward_pruning_saves_nodes_and_can_be_switched_off() {
+    let nodes = |pruning: Pruning| {
+        let mut game = Game::new();
+        let limits = SearchLimits {
+            pruning,
+            ..SearchLimits::depth(4)
+        };
+        find_best_move(&mut game, &limits, &Arc::new(AtomicBool::new(false))).nodes
+    };
+    let none = Pruning {
+        reverse_futility: false,
+        razoring: false,
+        futility: false,
+        late_move: false,
+    };
+
+    assert!(nodes(Pruning::default()) < nodes(none));
+}
+
+#[test]
+fn narrow_windows_save_nodes_on_the_bench() {
+    let nodes = |narrow_windows: bool| {
+        let mut total = 0;
+        for fen in BENCH_POSITIONS {
+            let mut game = Game::from_fen(fen).expect("valid FEN");
+            let limits = SearchLimits {
+                narrow_windows,
+                ..SearchLimits::depth(DEFAULT_BENCH_DEPTH)
+            };
+            total += find_best_move(&mut game, &limits, &Arc::new(AtomicBool::new(false))).nodes;
+        }
+        total
+    };
+
+    let (narrow, full) = (nodes(true), nodes(false));
+    assert!(narrow < full, "{narrow} nodes with narrow windows, {full} without");
+}
+
+#[test]
+fn node_limit_stops_near_the_budget() {
+    let nodes = |budget: u64| {
+        let mut game = Game::new();
+        let limits = SearchLimits {
+            nodes: Some(budget),
+            ..SearchLimits::default()
+        };
+        let result = find_best_move(&mut game, &limits, &Arc::new(AtomicBool::new(false)));
+        assert!(result.best_move.is_some());
+        result.nodes
+    };
+    // The result comes from the last finished iteration, and the limit is checked on
+    // entering a node, so quiescence search may go a little over.
+    assert!(nodes(5000) <= 5500);
+    assert!((5500..=55_000).contains(&nodes(50_000)));
+}
+
+#[test]
+fn mate_limit_stops_once_the_mate_is_found() {
+    let search = |limits: SearchLimits| {
+        let mut game = Game::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").expect("valid FEN");
+        find_best_move(&mut game, &limits, &Arc::new(AtomicBool::new(false)))
+    };
+    let mate = search(SearchLimits {
+        mate: Some(2),
+        ..SearchLimits::default()
+    });
+    assert_eq!(mate_in(mate.score), Some(2));
+    assert!(mate.nodes < search(SearchLimits::depth(6)).nodes);
+}
+
+#[test]
+fn search_moves_restrict_the_best_move() {
+    let a2a3 = Game::parse_move("a2a3").unwrap();
+    let h2h3 = Game::parse_move("h2h3").unwrap();
+    for search_moves in [vec![a2a3], vec![a2a3, h2h3]] {
+        let mut game = Game::new();
+        let limits = SearchLimits {
+            search_moves: search_moves.clone(),
+            ..SearchLimits::depth(3)
+        };
+        let result = find_best_move(&mut game, &limits, &Arc::new(AtomicBool::new(false)));
+        assert!(search_moves.contains(&result.best_move.unwrap()));
+    }
+}
+
+#[test]
+fn ponder_search_runs_until_ponderhit_and_then_keeps_its_limits() {
+    // 6 s on the clock gives a soft limit of 200 ms and a hard limit of 600 ms.
+    let tc = TimeControl {
+        wtime: Some(6000),
+        ..TimeControl::default()
+    };
+    let time = TimeManager::new(&tc, true, 0);
+    let (soft, hard) = (time.soft_limit().unwrap(), time.hard_limit().unwrap());
+    let ponder = Arc::new(AtomicBool::new(true));
+    let stop_signal = Arc::new(AtomicBool::new(false));
+    let limits = SearchLimits {
+        time,
+        ponder: Some(Arc::clone(&ponder)),
+        ..SearchLimits::default()
+    };
+    let stop_clone = Arc::clone(&stop_signal);
+    let search = thread::spawn(move || find_best_move(&mut Game::new(), &limits, &stop_clone));
+
+    thread::sleep(hard + Duration::from_millis(400));
+    assert!(!search.is_finished(), "pondering must not stop on its own");
+
+    // What the UCI loop does on "ponderhit": end pondering and arm the hard limit.
+    let ponderhit = Instant::now();
+    ponder.store(false, Ordering::Relaxed);
+    thread::spawn(move || {
+        thread::sleep(hard);
+        stop_signal.store(true, Ordering::Relaxed);
+    });
+
+    let result = search.join().unwrap();
+    let thought = ponderhit.elapsed();
+    assert!(result.best_move.is_some());
+    assert!(thought >= soft, "moved {thought:?} after ponderhit");
+    assert!(thought < hard + Duration::from_millis(300), "moved {thought:?} after ponderhit");
+}
+
+#[test]
+fn lazy_smp_returns_a_legal_move() {
+    let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    for threads in [2, 4] {
+        let mut game = Game::from_fen(kiwipete).expect("valid FEN");
+        let hash = game.zobrist_hash;
+        let limits = SearchLimits {
+            threads,
+            ..SearchLimits::depth(5)
+        };
+        let result = find_best_move(&mut game, &limits, &Arc::new(AtomicBool::new(false)));
+
+        assert_eq!(game.zobrist_hash, hash, "the main thread must unmake every move");
+        let best_move = result.best_move.expect("a move");
+        assert!(game.generate_legal_moves().iter().any(|&m| m == best_move));
+        assert_eq!(result.pv.first(), Some(&best_move));
+        assert!(result.nodes > 0);
+    }
+
+    // Helpers must not make the main thread miss a mate.
+    let mut game = Game::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").expect("valid FEN");
+    let limits = SearchLimits {
+        threads: 4,
+        ..SearchLimits::depth(6)
+    };
+    let result = find_best_move(&mut game, &limits, &Arc::new(AtomicBool::new(false)));
+    assert_eq!(mate_in(result.score), Some(2));
+}
+
+#[test]
+fn principal_variation_is_legal_and_starts_with_the_best_move() {
+    let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    for fen in [kiwipete, "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1"] {
+        let mut game = Game::from_fen(fen).expect("valid FEN");
+        let stop_signal = Arc::new(AtomicBool::new(false));
+        let result = find_best_move(&mut game, &SearchLimits::depth(5), &stop_signal);
+        assert_eq!(result.pv.first().copied(), result.best_move);
+        assert!(result.pv.len() > 1, "{fen}: {:?}", result.pv);
+
+        for &(from, to, promo) in &result.pv {
+            assert!(
+                game.generate_legal_moves().iter().any(|&m| m == (from, to, promo)),
+                "{fen}: illegal PV move {from}-{to}"
+            );
+            game.make_move_unchecked(from, to, promo);
+        }
+    }
+}
+
+#[test]
+fn null_moves_do_not_hide_pawn_ending_zugzwang() {
+    // White wins by taking the opposition, which only works because black has to move.
+    // Letting black pass the turn instead scores it as little more than a pawn up.
+    let mut game = Game::from_fen("8/8/8/4K3/4P2k/8/8/8 w - - 0 1").expect("valid FEN");
+    let stop_signal = Arc::new(AtomicBool::new(false));
+    let result = find_best_move(&mut game, &SearchLimits::depth(8), &stop_signal);
+    assert!(result.score > 500, "{}", result.score);
+}
+
+#[test]
+fn null_move_verification_finds_the_zugzwang_draw() {
+    // Only Rf1 holds the draw. It relies on zugzwang while black still has its rook, so null
+    // moves stay allowed and the deep cutoffs have to be verified to see it.
+    let fen = "8/8/p1p5/1p5p/1P5p/8/PPP2K1p/4R1rk w - - 0 1";
+    let mut game = Game::from_fen(fen).expect("valid FEN");
+    let stop_signal = Arc::new(AtomicBool::new(false));
+    let result = find_best_move(&mut game, &SearchLimits::depth(12), &stop_signal);
+    assert_eq!(result.best_move, Game::parse_move("e1f1"));
+}
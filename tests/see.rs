@@ -0,0 +1,24 @@
+use chess_bot::Game;
+use chess_bot::search::see::static_exchange_exchange;
+
+fn see(fen: &str, from: usize, to: usize) -> i32 {
+    static_exchange_exchange(&Game::from_fen(fen).expect("valid FEN"), from, to)
+}
+
+#[test]
+fn winning_an_undefended_piece() {
+    // exd5 takes a knight nobody defends.
+    assert_eq!(see("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1", 28, 35), 320);
+}
+
+#[test]
+fn trading_pawns() {
+    // exd5 cxd5.
+    assert_eq!(see("4k3/8/2p5/3p4/4P3/8/8/4K3 w - - 0 1", 28, 35), 0);
+}
+
+#[test]
+fn pawn_takes_a_defended_knight() {
+    // exd5 cxd5 still wins a knight for a pawn.
+    assert_eq!(see("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1", 28, 35), 320 - 100);
+}
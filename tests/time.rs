@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use chess_bot::search::time::{TimeControl, TimeManager};
+
+/// Soft and hard limit in milliseconds for white, or `None` without a limit.
+fn limits(tc: TimeControl, move_overhead: u64) -> (Option<u128>, Option<u128>) {
+    let tm = TimeManager::new(&tc, true, move_overhead);
+    (
+        tm.soft_limit().map(|d| d.as_millis()),
+        tm.hard_limit().map(|d| d.as_millis()),
+    )
+}
+
+#[test]
+fn movetime_is_used_up_to_the_overhead() {
+    let tc = TimeControl {
+        movetime: Some(1000),
+        ..TimeControl::default()
+    };
+    assert_eq!(limits(tc, 50), (Some(950), Some(950)));
+    // Never a zero limit, even if the overhead eats the whole move time.
+    assert_eq!(limits(tc, 2000), (Some(1), Some(1)));
+}
+
+#[test]
+fn clock_is_split_over_thirty_moves_by_default() {
+    let tc = TimeControl {
+        wtime: Some(60_000),
+        ..TimeControl::default()
+    };
+    assert_eq!(limits(tc, 0), (Some(2000), Some(6000)));
+    assert_eq!(limits(tc, 3000), (Some(1900), Some(5700)));
+}
+
+#[test]
+fn increment_adds_three_quarters() {
+    let tc = TimeControl {
+        wtime: Some(30_000),
+        winc: 1000,
+        ..TimeControl::default()
+    };
+    assert_eq!(limits(tc, 0), (Some(1750), Some(5250)));
+}
+
+#[test]
+fn movestogo_is_clamped() {
+    let tc = |movestogo| TimeControl {
+        wtime: Some(50_000),
+        movestogo: Some(movestogo),
+        ..TimeControl::default()
+    };
+    assert_eq!(limits(tc(10), 0), (Some(5000), Some(15_000)));
+    assert_eq!(limits(tc(200), 0), limits(tc(50), 0));
+    assert_eq!(limits(tc(200), 0), (Some(1000), Some(3000)));
+    assert_eq!(limits(tc(0), 0), limits(tc(1), 0));
+}
+
+#[test]
+fn limits_are_capped_at_eighty_percent_of_the_clock() {
+    let tc = TimeControl {
+        wtime: Some(10_000),
+        movestogo: Some(1),
+        ..TimeControl::default()
+    };
+    assert_eq!(limits(tc, 0), (Some(8000), Some(8000)));
+
+    // The hard limit is three times the soft one, but still within the cap.
+    let tc = TimeControl {
+        wtime: Some(10_000),
+        movestogo: Some(3),
+        ..TimeControl::default()
+    };
+    assert_eq!(limits(tc, 0), (Some(3333), Some(8000)));
+}
+
+#[test]
+fn clock_shorter_than_the_overhead_still_moves() {
+    let tc = TimeControl {
+        wtime: Some(20),
+        winc: 0,
+        ..TimeControl::default()
+    };
+    assert_eq!(limits(tc, 50), (Some(1), Some(1)));
+}
+
+#[test]
+fn side_to_move_picks_its_own_clock() {
+    let tc = TimeControl {
+        wtime: Some(1000),
+        btime: Some(60_000),
+        winc: 5000,
+        binc: 0,
+        ..TimeControl::default()
+    };
+    let tm = TimeManager::new(&tc, false, 0);
+    assert_eq!(tm.soft_limit(), Some(Duration::from_millis(2000)));
+    assert_eq!(tm.hard_limit(), Some(Duration::from_millis(6000)));
+}
+
+#[test]
+fn no_clock_means_no_limit() {
+    let tc = TimeControl {
+        btime: Some(60_000),
+        ..TimeControl::default()
+    };
+    assert_eq!(limits(tc, 0), (None, None));
+    assert!(!TimeManager::new(&tc, true, 0).soft_limit_reached());
+}
@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use chess_bot::Game;
+use chess_bot::search::tt::{Flag, TranspositionTable};
+
+#[test]
+fn fresh_tables_are_empty_and_usable() {
+    for mb in [1, 3, 16] {
+        let tt = TranspositionTable::with_size_mb(mb);
+        assert_eq!(tt.hashfull(), 0);
+
+        let keys: Vec<u64> = (1..=1000u64).map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15)).collect();
+        assert!(keys.iter().all(|&key| tt.probe(key).is_none()));
+
+        for &key in &keys {
+            tt.store(key, 5, 42, Flag::Exact, Some((12, 28, None)), Some(7));
+        }
+        // Every bucket holds a few entries, most keys must survive in a table this size.
+        let found = keys.iter().filter_map(|&key| tt.probe(key)).collect::<Vec<_>>();
+        assert!(found.len() > 900, "{mb} MB kept {}", found.len());
+        for entry in found {
+            assert_eq!((entry.depth, entry.score, entry.flag), (5, 42, Flag::Exact));
+            assert_eq!(entry.best_move, Some((12, 28, None)));
+            assert_eq!(entry.static_eval, Some(7));
+        }
+    }
+}
+
+#[test]
+fn creating_games_does_not_touch_the_whole_table() {
+    // Each game owns a default size table, clearing it by hand used to take ~90ms a game.
+    let start = Instant::now();
+    for _ in 0..50 {
+        let game = Game::new();
+        assert!(game.tt.probe(game.zobrist_hash).is_none());
+    }
+    assert!(start.elapsed() < Duration::from_secs(2), "{:?}", start.elapsed());
+}
+
+#[test]
+fn entries_are_verified_by_the_key() {
+    let tt = TranspositionTable::with_size_mb(1);
+    let key = 0x0123_4567_89AB_CDEF;
+    tt.store(key, 4, 10, Flag::LowerBound, None, None);
+    assert!(tt.probe(key).is_some());
+    // Same bucket, different check bits.
+    assert!(tt.probe(key ^ 1).is_none());
+    assert!(tt.probe(key ^ 0x8000).is_none());
+}
+
+#[test]
+fn deepest_entries_can_be_replaced() {
+    let tt = TranspositionTable::with_size_mb(1);
+    let key = 0xFEDC_BA98_7654_3210;
+    tt.store(key, u8::MAX, 10, Flag::LowerBound, None, None);
+    tt.store(key, u8::MAX - 1, 20, Flag::UpperBound, None, None);
+    let entry = tt.probe(key).expect("stored");
+    assert_eq!((entry.depth, entry.score), (u8::MAX - 1, 20));
+}
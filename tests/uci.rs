@@ -0,0 +1,175 @@
+use std::io::{BufRead, BufReader, Lines, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The engine binary, driven over its standard input and output.
+struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Engine {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_chess_bot"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("engine starts");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+        Self {
+            child,
+            stdin,
+            stdout,
+        }
+    }
+
+    fn send(&mut self, command: &str) {
+        writeln!(self.stdin, "{command}").unwrap();
+    }
+
+    /// Reads output up to and including the first line starting with `prefix`.
+    fn read_until(&mut self, prefix: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        for line in self.stdout.by_ref() {
+            let line = line.unwrap();
+            let done = line.starts_with(prefix);
+            lines.push(line);
+            if done {
+                return lines;
+            }
+        }
+        panic!("engine exited before {prefix}: {lines:?}");
+    }
+
+    /// Runs `go depth <depth>` and returns its `info depth` lines.
+    fn info_lines(&mut self, depth: u8) -> Vec<String> {
+        self.send(&format!("go depth {depth}"));
+        let mut output = self.read_until("bestmove");
+        output.retain(|line| line.starts_with("info depth"));
+        output
+    }
+
+    /// Runs `go depth <depth>` and returns the nodes of the last info line.
+    fn nodes_to_depth(&mut self, depth: u8) -> u64 {
+        self.send(&format!("go depth {depth}"));
+        let output = self.read_until("bestmove");
+        let info = output
+            .iter()
+            .rev()
+            .find(|line| line.starts_with("info depth"))
+            .expect("an info line");
+        let mut tokens = info.split_whitespace();
+        tokens.find(|&t| t == "nodes");
+        tokens.next().unwrap().parse().unwrap()
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "quit");
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn hash_size_is_clamped_and_reported() {
+    let mut engine = Engine::start();
+    engine.send("setoption name Hash value 100000");
+    assert_eq!(engine.read_until("info string"), ["info string Hash size set to 2048 MB"]);
+    engine.send("setoption name Hash value 0");
+    assert_eq!(engine.read_until("info string"), ["info string Hash size set to 1 MB"]);
+}
+
+#[test]
+fn resizing_and_clear_hash_empty_the_table() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    let cold = engine.nodes_to_depth(7);
+    // The same search again mostly runs on table hits.
+    let warm = engine.nodes_to_depth(7);
+    assert!(warm * 4 < cold, "{warm} nodes warm, {cold} cold");
+
+    engine.send("setoption name Clear Hash");
+    assert!(engine.nodes_to_depth(7) > warm * 4);
+
+    engine.nodes_to_depth(7);
+    engine.send("setoption name Hash value 16");
+    engine.read_until("info string");
+    assert!(engine.nodes_to_depth(7) > warm * 4);
+}
+
+/// Splits an info line into its fields, checking they come in the usual order.
+fn info_fields(line: &str) -> Vec<(&str, Vec<&str>)> {
+    let keys = [
+        "depth", "seldepth", "multipv", "score", "wdl", "nodes", "nps", "hashfull", "time", "pv",
+    ];
+    let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+    for token in line.split_whitespace().skip(1) {
+        match fields.last_mut() {
+            Some((_, values)) if !keys.contains(&token) => values.push(token),
+            _ => fields.push((token, Vec::new())),
+        }
+    }
+    let order: Vec<&str> = fields.iter().map(|(key, _)| *key).collect();
+    assert_eq!(order, keys, "{line}");
+    fields
+}
+
+#[test]
+fn info_lines_report_score_wdl_and_hashfull() {
+    let mut engine = Engine::start();
+    engine.send("setoption name UCI_ShowWDL value true");
+    engine.send("setoption name Hash value 1");
+    engine.read_until("info string");
+    engine.send("position startpos");
+
+    let lines = engine.info_lines(6);
+    assert_eq!(lines.len(), 6);
+    let mut hashfull = Vec::new();
+    for (depth, line) in (1..).zip(&lines) {
+        let fields = info_fields(line);
+        assert_eq!(fields[0].1, [depth.to_string()]);
+        assert!(fields[1].1[0].parse::<u32>().unwrap() >= depth, "{line}");
+        assert_eq!(fields[3].1[0], "cp", "{line}");
+        let wdl: Vec<u32> = fields[4].1.iter().map(|v| v.parse().unwrap()).collect();
+        assert_eq!(wdl.len(), 3);
+        assert_eq!(wdl.iter().sum::<u32>(), 1000, "{line}");
+        // Even positions are mostly drawn.
+        assert!(wdl[1] > 500, "{line}");
+        hashfull.push(fields[7].1[0].parse::<u32>().unwrap());
+        assert!(!fields[9].1.is_empty(), "{line}");
+    }
+    assert!(hashfull.windows(2).all(|w| w[0] <= w[1]), "{hashfull:?}");
+    assert!((1..=1000).contains(hashfull.last().unwrap()), "{hashfull:?}");
+
+    engine.send("position fen 6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+    let lines = engine.info_lines(3);
+    let fields = info_fields(lines.last().unwrap());
+    assert_eq!(fields[3].1, ["mate", "1"]);
+    assert_eq!(fields[4].1, ["1000", "0", "0"]);
+    assert_eq!(fields[9].1, ["a1a8"]);
+}
+
+#[test]
+fn closing_the_input_ends_the_engine() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chess_bot"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("engine starts");
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "position startpos").unwrap();
+    drop(stdin);
+
+    let start = Instant::now();
+    while child.try_wait().unwrap().is_none() {
+        if start.elapsed() > Duration::from_secs(5) {
+            child.kill().unwrap();
+            panic!("engine kept running without input");
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,62 @@
+use chess_bot::Game;
+use chess_bot::search::zobrist::compute_zobrist_hash;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const POSITIONS: [&str; 3] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+];
+
+#[test]
+fn side_to_move_changes_the_hash() {
+    let white = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let mut black = Game::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+    assert_ne!(white.zobrist_hash, black.zobrist_hash);
+
+    black.make_move_unchecked(60, 59, None);
+    let mut white = Game::from_fen("3k4/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(black.zobrist_hash, white.zobrist_hash);
+    white.make_move_unchecked(4, 3, None);
+    assert_eq!(white.zobrist_hash, compute_zobrist_hash(white.clone()));
+}
+
+#[test]
+fn incremental_hash_matches_recomputed_hash() {
+    let mut rng = StdRng::seed_from_u64(0x2057);
+    for fen in POSITIONS {
+        for _ in 0..20 {
+            let mut game = Game::from_fen(fen).unwrap();
+            let mut undos = Vec::new();
+            let mut hashes = vec![game.zobrist_hash];
+            for _ in 0..30 {
+                let moves: Vec<_> = game.generate_legal_moves().iter().copied().collect();
+                if moves.is_empty() {
+                    break;
+                }
+                let (from, to, promo) = moves[rng.random_range(0..moves.len())];
+                undos.push(game.make_move_unchecked(from, to, promo));
+                assert_eq!(game.zobrist_hash, compute_zobrist_hash(game.clone()), "{fen}");
+                hashes.push(game.zobrist_hash);
+            }
+            while let Some(undo) = undos.pop() {
+                hashes.pop();
+                game.unmake_move(undo);
+                assert_eq!(Some(&game.zobrist_hash), hashes.last());
+                assert_eq!(game.zobrist_hash, compute_zobrist_hash(game.clone()), "{fen}");
+            }
+        }
+    }
+}
+
+#[test]
+fn castling_hashes_the_rook() {
+    let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    for (from, to) in [(4, 6), (60, 58)] {
+        game.make_move_unchecked(from, to, None);
+        assert_eq!(game.zobrist_hash, compute_zobrist_hash(game.clone()));
+    }
+    let castled = Game::from_fen("2kr3r/8/8/8/8/8/8/R4RK1 w - - 2 2").unwrap();
+    assert_eq!(game.zobrist_hash, castled.zobrist_hash);
+}